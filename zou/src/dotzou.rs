@@ -0,0 +1,213 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+
+/// Name of the per-project config file, read from the current directory
+pub const FILE_NAME: &str = ".zou";
+
+/// Path of the global config file, `$HOME/.config/zou/config`
+pub fn global_path() -> Option<PathBuf> {
+    home::home_dir().map(|home_dir| home_dir.join(".config").join("zou").join("config"))
+}
+
+/// Reads the value of `key` in the dotenv file at `path`, if it is set there
+pub fn get_var(path: &Path, key: &str) -> anyhow::Result<Option<String>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).with_context(|| format!("unable to read {path:?}")),
+    };
+
+    Ok(content.lines().rev().find_map(|line| {
+        let assignment = line.trim_start();
+        let assignment = assignment.strip_prefix("export ").unwrap_or(assignment);
+        match assignment.split_once('=') {
+            Some((name, value)) if name.trim() == key => {
+                Some(value.trim().trim_matches(['"', '\'']).to_string())
+            }
+            _ => None,
+        }
+    }))
+}
+
+/// Variables of the global config for `profile`, those set before any `[section]` followed by
+/// those of the `[profile]` section
+pub fn profile_vars(content: &str, profile: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let mut section = None;
+    let mut found = false;
+    let mut vars = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            let name = name.trim();
+            found |= name == profile;
+            section = Some(name.to_string());
+            continue;
+        }
+        if section.as_deref().is_some_and(|section| section != profile) {
+            continue;
+        }
+        let assignment = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = assignment.split_once('=') else {
+            bail!("invalid line {line:?}");
+        };
+        let value = value.trim().trim_matches(['"', '\'']);
+        vars.push((key.trim().to_string(), value.to_string()));
+    }
+    if !found {
+        bail!("no [{profile}] profile");
+    }
+    Ok(vars)
+}
+
+/// Sets `key=value` in the dotenv file at `path`, keeping its other lines as they are
+///
+/// The file is created when it does not exist yet. Values with spaces or characters dotenv would
+/// interpret, like `$`, are single-quoted.
+pub fn set_var(path: &Path, key: &str, value: &str) -> anyhow::Result<()> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err).with_context(|| format!("unable to read {path:?}")),
+    };
+
+    let entry = format!("{key}={}", quote(value));
+    let mut found = false;
+    let mut lines: Vec<&str> = content
+        .lines()
+        .map(|line| {
+            let assignment = line.trim_start();
+            let assignment = assignment.strip_prefix("export ").unwrap_or(assignment);
+            match assignment.split_once('=') {
+                Some((name, _)) if name.trim() == key => {
+                    found = true;
+                    entry.as_str()
+                }
+                _ => line,
+            }
+        })
+        .collect();
+    if !found {
+        lines.push(&entry);
+    }
+
+    let mut updated = lines.join("\n");
+    updated.push('\n');
+    if updated != content {
+        std::fs::write(path, updated).with_context(|| format!("unable to write {path:?}"))?;
+    }
+    Ok(())
+}
+
+/// `value` as dotenv reads it back verbatim, `'` being escaped out of the quotes
+fn quote(value: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "/._-:,@+%".contains(c);
+    if !value.is_empty() && value.chars().all(plain) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+#[test]
+fn test_set_var() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(FILE_NAME);
+
+    set_var(&path, "ZOU_NAME", "silly-goose-42").unwrap();
+    assert_eq!(
+        "ZOU_NAME=silly-goose-42\n",
+        std::fs::read_to_string(&path).unwrap()
+    );
+
+    std::fs::write(&path, "ZOU_USER=zou\n# comment\nexport ZOU_NAME=old\n").unwrap();
+    set_var(&path, "ZOU_NAME", "new").unwrap();
+    assert_eq!(
+        "ZOU_USER=zou\n# comment\nZOU_NAME=new\n",
+        std::fs::read_to_string(&path).unwrap()
+    );
+
+    std::fs::write(&path, "ZOU_HOST=example.com").unwrap();
+    set_var(&path, "ZOU_NAME", "new").unwrap();
+    assert_eq!(
+        "ZOU_HOST=example.com\nZOU_NAME=new\n",
+        std::fs::read_to_string(&path).unwrap()
+    );
+}
+
+#[test]
+fn test_get_var() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(FILE_NAME);
+    assert_eq!(None, get_var(&path, "ZOU_USER").unwrap());
+
+    std::fs::write(
+        &path,
+        "ZOU_USER=zou\n# ZOU_HOST=nope\nexport ZOU_HOST=\"example.com\"\n",
+    )
+    .unwrap();
+    assert_eq!(Some("zou".to_string()), get_var(&path, "ZOU_USER").unwrap());
+    assert_eq!(
+        Some("example.com".to_string()),
+        get_var(&path, "ZOU_HOST").unwrap()
+    );
+    assert_eq!(None, get_var(&path, "ZOU_UPLOAD_DIR").unwrap());
+}
+
+#[test]
+fn test_profile_vars() {
+    let config = "ZOU_USER=zou\n\n[staging]\nZOU_HOST=staging.example.com\n\n[prod]\n# live\nZOU_HOST=example.com\nexport ZOU_USER='deploy'\n";
+    let vars = |profile| profile_vars(config, profile).unwrap();
+    let var = |key: &str, value: &str| (key.to_string(), value.to_string());
+
+    assert_eq!(
+        vec![
+            var("ZOU_USER", "zou"),
+            var("ZOU_HOST", "staging.example.com")
+        ],
+        vars("staging")
+    );
+    assert_eq!(
+        vec![
+            var("ZOU_USER", "zou"),
+            var("ZOU_HOST", "example.com"),
+            var("ZOU_USER", "deploy")
+        ],
+        vars("prod")
+    );
+    assert!(profile_vars(config, "dev").is_err());
+    assert!(profile_vars("[prod]\nZOU_HOST\n", "prod").is_err());
+}
+
+#[test]
+fn test_set_var_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(FILE_NAME);
+    let values = [
+        ("ZOU_IDENTITY", "/home/me/deploy key"),
+        ("ZOU_UPLOAD_DIR", "/srv/$HOME/it's"),
+        ("ZOU_HOST", "example.com"),
+        ("ZOU_USER", ""),
+    ];
+    for (key, value) in values {
+        set_var(&path, key, value).unwrap();
+    }
+
+    let read: Vec<(String, String)> = dotenvy::from_path_iter(&path)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let expected: Vec<(String, String)> = values
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    assert_eq!(expected, read);
+    assert_eq!(
+        Some("/home/me/deploy key".to_string()),
+        get_var(&path, "ZOU_IDENTITY").unwrap()
+    );
+}
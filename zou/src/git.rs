@@ -0,0 +1,95 @@
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Context};
+use tempfile::TempDir;
+
+use crate::runner::spawn_error;
+
+/// Extracts the content of `dir` at the commit `reference` in a temporary directory, removed when
+/// dropped
+///
+/// Only committed files are published that way, untracked files and local edits being left out.
+pub fn archive(dir: &Path, reference: &str) -> anyhow::Result<TempDir> {
+    let mut rev_parse = Command::new("git");
+    rev_parse
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--show-toplevel", "--show-prefix"]);
+    let location = rev_parse
+        .output()
+        .map_err(|error| spawn_error(&rev_parse, error))?;
+    if !location.status.success() {
+        bail!("{dir:?} is not a git repository");
+    }
+    let location = String::from_utf8_lossy(&location.stdout);
+    let mut lines = location.lines();
+    let (root, prefix) = (
+        lines.next().unwrap_or("."),
+        lines.next().unwrap_or_default(),
+    );
+
+    // "<ref>:<prefix>" is the tree of `dir` itself, which may be below the root of the repository
+    let mut git_archive = Command::new("git");
+    git_archive
+        .args(["-C", root, "archive", "--format=tar"])
+        .arg(format!("{reference}:{prefix}"));
+    let archive = git_archive
+        .output()
+        .map_err(|error| spawn_error(&git_archive, error))?;
+    if !archive.status.success() {
+        bail!(
+            "unable to archive {reference}: {}",
+            String::from_utf8_lossy(&archive.stderr).trim()
+        );
+    }
+
+    let checkout = tempfile::Builder::new()
+        .prefix("zou-")
+        .tempdir()
+        .context("unable to create a temporary directory")?;
+    tar::Archive::new(archive.stdout.as_slice())
+        .unpack(checkout.path())
+        .with_context(|| format!("unable to extract {reference}"))?;
+    Ok(checkout)
+}
+
+#[test]
+fn test_archive() {
+    let repo = tempfile::tempdir().unwrap();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(repo.path())
+            .args(["-c", "user.name=zou", "-c", "user.email=zou@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {args:?}");
+    };
+    assert!(archive(repo.path(), "HEAD").is_err());
+
+    git(&["init", "-q"]);
+    std::fs::create_dir(repo.path().join("www")).unwrap();
+    std::fs::write(repo.path().join("www/index.html"), "committed").unwrap();
+    std::fs::write(repo.path().join("README"), "readme").unwrap();
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "first"]);
+    std::fs::write(repo.path().join("www/index.html"), "edited").unwrap();
+    std::fs::write(repo.path().join("www/draft.html"), "untracked").unwrap();
+
+    let checkout = archive(&repo.path().join("www"), "HEAD").unwrap();
+    assert_eq!(
+        "committed",
+        std::fs::read_to_string(checkout.path().join("index.html")).unwrap()
+    );
+    assert!(!checkout.path().join("draft.html").exists());
+    assert!(!checkout.path().join("README").exists());
+
+    let path = checkout.path().to_path_buf();
+    drop(checkout);
+    assert!(!path.exists());
+
+    assert!(archive(repo.path(), "no-such-ref").is_err());
+}
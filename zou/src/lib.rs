@@ -1 +1,7 @@
-pub mod registry;
\ No newline at end of file
+pub mod dotzou;
+pub mod git;
+pub mod manifest;
+pub mod registry;
+pub mod runner;
+pub mod status;
+pub mod upload;
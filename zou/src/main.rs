@@ -1,7 +1,16 @@
-use std::path::PathBuf;
+use std::io::{BufRead, IsTerminal, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 
-use clap::{Parser, Subcommand};
-use zou::registry::Registry;
+use anyhow::Context;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use zou::{
+    dotzou, git, manifest,
+    registry::{self, NameStyle, Registry},
+    status,
+    upload::HttpUpload,
+};
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -9,18 +18,99 @@ struct Args {
     #[clap(long, short, help = "Debug mode", default_value = "false")]
     debug: bool,
 
+    #[clap(
+        long,
+        short,
+        help = "Only print the URL of what is published, for scripts",
+        default_value = "false"
+    )]
+    quiet: bool,
+
+    #[clap(
+        long,
+        help = "Show the commands to run without changing anything on the registry",
+        default_value = "false"
+    )]
+    dry_run: bool,
+
     #[clap(long, help = "SSH user", env = "ZOU_USER")]
-    user: String,
+    user: Option<String>,
+
+    #[clap(
+        long,
+        help = "Section of the global config to take the settings from",
+        env = "ZOU_PROFILE"
+    )]
+    profile: Option<String>,
 
     #[clap(long, help = "SSH host", env = "ZOU_HOST")]
-    host: String,
+    host: Option<String>,
+
+    #[clap(
+        long,
+        help = "Print https:// URLs, for registries serving the sites over TLS",
+        env = "ZOU_HTTPS",
+        default_value = "false"
+    )]
+    https: bool,
+
+    #[clap(
+        long,
+        help = "Public URL of the sites, like https://{name}.sites.example.com, when not served by --host",
+        env = "ZOU_PUBLIC_BASE",
+        value_parser = parse_public_base
+    )]
+    public_base: Option<String>,
+
+    #[clap(long, help = "SSH port", env = "ZOU_SSH_PORT", default_value = "22")]
+    port: u16,
+
+    #[clap(long, help = "SSH identity file", env = "ZOU_IDENTITY")]
+    identity: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Style of the generated names",
+        env = "ZOU_NAME_STYLE",
+        default_value = "numbered"
+    )]
+    name_style: NameStyle,
+
+    #[clap(
+        long,
+        help = "File of words, one per line, to generate names from",
+        env = "ZOU_WORDLIST"
+    )]
+    wordlist: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Times to retry commands failing on the network",
+        default_value = "0"
+    )]
+    retries: u32,
 
     #[clap(
         long,
         help = "Path to registry's upload directory",
         env = "ZOU_UPLOAD_DIR"
     )]
-    upload_dir: String,
+    upload_dir: Option<String>,
+
+    #[clap(
+        long,
+        help = "zoubida upload endpoint to publish to over HTTP instead of SSH",
+        env = "ZOU_UPLOAD_URL",
+        requires = "upload_token"
+    )]
+    upload_url: Option<String>,
+
+    #[clap(
+        long,
+        help = "Token of the zoubida upload endpoint",
+        env = "ZOU_UPLOAD_TOKEN"
+    )]
+    upload_token: Option<String>,
 
     #[clap(subcommand)]
     cmd: Option<Cmd>,
@@ -30,67 +120,673 @@ struct Args {
 enum Cmd {
     #[clap(aliases = ["p", "pub"])]
     Publish {
-        #[clap(help = "Directory to publish")]
+        #[clap(help = "Directory to publish", conflicts_with = "manifest")]
         dir: Option<PathBuf>,
 
         #[clap(help = "Optional name of the project", env = "ZOU_NAME")]
         name: Option<String>,
 
-        #[clap(long, help = "Deletes the previous content before publishing")]
+        #[clap(long, help = "Deletes remote files that no longer exist locally")]
         force: bool,
+
+        #[clap(
+            long,
+            help = "Pattern of files not to publish, in addition to those of .zouignore"
+        )]
+        exclude: Vec<String>,
+
+        #[clap(long, help = "Skips checking whether the name is already taken")]
+        no_check: bool,
+
+        #[clap(
+            long,
+            value_name = "REF",
+            help = "Publishes the directory as committed at REF instead of the working tree",
+            num_args = 0..=1,
+            default_missing_value = "HEAD",
+            conflicts_with = "manifest"
+        )]
+        from_git: Option<String>,
+
+        #[clap(
+            long,
+            help = "Skips checking that the upload directory exists and is writable"
+        )]
+        no_preflight: bool,
+
+        #[clap(long, short, help = "Opens the published site in the browser")]
+        open: bool,
+
+        #[clap(
+            long,
+            help = "Shows the progress of the transfer, by default when stdout is a terminal"
+        )]
+        progress: bool,
+
+        #[clap(
+            long,
+            help = "Only print the name of what is published, for scripts",
+            conflicts_with = "open"
+        )]
+        print_name: bool,
+
+        #[clap(
+            long,
+            help = "Publishes every [[site]] of a zou.toml manifest instead of one directory"
+        )]
+        manifest: Option<PathBuf>,
+
+        #[clap(
+            long,
+            short,
+            help = "Number of sites of the manifest published at once",
+            default_value = "4"
+        )]
+        jobs: NonZeroUsize,
+
+        #[clap(
+            long,
+            help = "Upload bandwidth limit per second, like 500k or 2m",
+            value_parser = parse_bwlimit
+        )]
+        bwlimit: Option<String>,
     },
 
     #[clap(aliases = ["d", "rm", "del"])]
     Delete {
         #[clap(help = "Name of the project", env = "ZOU_NAME")]
         name: String,
+
+        #[clap(long, short, help = "Deletes without asking for confirmation")]
+        yes: bool,
+    },
+
+    #[clap(aliases = ["mv"])]
+    Rename {
+        #[clap(help = "Current name of the project")]
+        old: String,
+
+        #[clap(help = "New name of the project")]
+        new: String,
+    },
+
+    /// Streams the logs of the registry server until Ctrl-C
+    Logs {
+        #[clap(
+            long,
+            help = "Log file to tail, or else journald unit to follow",
+            env = "ZOU_LOG_SOURCE",
+            default_value = "zoubida"
+        )]
+        log_source: String,
+
+        #[clap(
+            long,
+            short = 'n',
+            help = "Number of past lines to show first",
+            default_value = "100"
+        )]
+        lines: u32,
+    },
+
+    #[clap(aliases = ["s", "st"])]
+    Status {
+        #[clap(help = "Name of the project", env = "ZOU_NAME")]
+        name: String,
+    },
+
+    /// Manages the .zou and global config files
+    Config {
+        #[clap(subcommand)]
+        cmd: ConfigCmd,
+    },
+
+    /// Prints the completion script of bash, zsh, fish, elvish or powershell
+    #[clap(hide = true)]
+    Completions {
+        #[clap(help = "Shell to complete zou in: bash, zsh, fish, elvish or powershell")]
+        shell: Shell,
     },
 
     #[clap(aliases = ["l", "ls"])]
-    List,
+    List {
+        #[clap(long, help = "Output format", default_value = "text")]
+        format: Format,
+
+        #[clap(long, help = "Print the names of the sites instead of their URLs")]
+        plain: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCmd {
+    /// Prompts for the registry settings and writes them to .zou
+    Init {
+        #[clap(long, help = "Writes the global ~/.config/zou/config instead of .zou")]
+        global: bool,
+    },
+}
+
+/// Settings prompted by `zou config init`: variable, question, and whether it is required
+const CONFIG_VARS: [(&str, &str, bool); 5] = [
+    ("ZOU_USER", "SSH user", true),
+    ("ZOU_HOST", "SSH host", true),
+    (
+        "ZOU_UPLOAD_DIR",
+        "Path to registry's upload directory",
+        true,
+    ),
+    ("ZOU_SSH_PORT", "SSH port (optional)", false),
+    ("ZOU_IDENTITY", "SSH identity file (optional)", false),
+];
+
+#[derive(ValueEnum, Clone, Debug)]
+enum Format {
+    Text,
+    Json,
 }
 
 fn main() -> anyhow::Result<()> {
-    load_dotenv();
+    load_dotenv()?;
 
     let args = Args::parse();
 
-    let mut registry = Registry::new(&args.user, &args.host, &args.upload_dir);
+    // these need none of the registry settings
+    match &args.cmd {
+        Some(Cmd::Config { cmd }) => return run_config(cmd),
+        Some(Cmd::Completions { shell }) => {
+            print_completions(*shell);
+            return Ok(());
+        }
+        _ => {}
+    }
+    check_registry_args(&args)?;
+    let host = args.host.as_deref().unwrap_or_default();
+
+    let mut registry = Registry::new(
+        args.user.as_deref().unwrap_or_default(),
+        host,
+        args.port,
+        args.upload_dir.as_deref().unwrap_or_default(),
+    );
+    if args.debug {
+        if let Some(profile) = &args.profile {
+            eprintln!("using the [{profile}] profile");
+        }
+    }
     registry.debug = args.debug;
+    registry.dry_run = args.dry_run;
+    registry.quiet = args.quiet;
+    registry.https = args.https;
+    registry.public_base = args.public_base.clone();
+    registry.identity = args.identity.clone();
+    registry.retries = args.retries;
+    registry.name_style = args.name_style;
+    if let Some(wordlist) = &args.wordlist {
+        registry.wordlist = registry::load_wordlist(wordlist)?;
+    }
+    if let (Some(url), Some(token)) = (&args.upload_url, &args.upload_token) {
+        registry.upload = Some(HttpUpload {
+            url: url.clone(),
+            token: token.clone(),
+        });
+    }
+    // the name saved in .zou is the project's own, republishing to it is no clash
+    let local_config = std::env::current_dir()?.join(dotzou::FILE_NAME);
+    registry.saved_name = dotzou::get_var(&local_config, "ZOU_NAME")?;
 
     match args.cmd {
         None => {
+            // bare `zou` republishes the project saved in .zou
+            registry.check_exists = false;
+            registry.progress = !args.quiet && std::io::stdout().is_terminal();
             let name = std::env::var("ZOU_NAME").ok();
-            registry.publish(name.as_deref(), None)?;
+            let name = registry.publish(name.as_deref(), None, false)?;
+            if args.quiet {
+                println!("{}", registry.url(&name));
+            }
+            if !args.dry_run {
+                save_name(&name)?;
+            }
         }
-        Some(Cmd::Publish { dir, name, force }) => {
-            if force {
-                if let Some(name) = name.as_deref() {
-                    registry.delete(name)?;
+        Some(Cmd::Publish {
+            force,
+            exclude,
+            bwlimit,
+            no_check,
+            no_preflight,
+            open,
+            progress,
+            print_name,
+            manifest: Some(manifest),
+            jobs,
+            ..
+        }) => {
+            registry.quiet |= print_name;
+            // the progress lines of concurrent transfers would be garbled
+            registry.progress =
+                jobs.get() == 1 && !registry.quiet && (progress || std::io::stdout().is_terminal());
+            registry.check_exists = !no_check;
+            registry.preflight = !no_preflight;
+            registry.excludes = exclude;
+            registry.bwlimit = bwlimit;
+            let sites = manifest::load(&manifest)?;
+            let results = registry.publish_all(&sites, force, jobs.get());
+            let mut failures = 0;
+            for (site, published) in sites.iter().zip(results) {
+                match published {
+                    Ok(name) if print_name => println!("{name}"),
+                    Ok(name) if args.quiet => println!("{}", registry.url(&name)),
+                    Ok(name) if open && !args.dry_run => open_in_browser(&registry.url(&name)),
+                    Ok(_) => {}
+                    Err(err) => {
+                        failures += 1;
+                        eprintln!("✘ {}: {err:#}", site.name);
+                    }
                 }
             }
-            let _name = registry.publish(name.as_deref(), dir)?;
-            // TODO save ZOU_NAME=<name> in .zou
+            if failures > 0 {
+                anyhow::bail!("{failures} of {} sites failed to publish", sites.len());
+            }
         }
-        Some(Cmd::Delete { name }) => {
+        Some(Cmd::Publish {
+            dir,
+            name,
+            force,
+            exclude,
+            bwlimit,
+            no_check,
+            no_preflight,
+            open,
+            progress,
+            print_name,
+            from_git,
+            manifest: None,
+            ..
+        }) => {
+            registry.quiet |= print_name;
+            registry.progress = !registry.quiet && (progress || std::io::stdout().is_terminal());
+            registry.check_exists = !no_check;
+            registry.preflight = !no_preflight;
+            registry.excludes = exclude;
+            registry.bwlimit = bwlimit;
+            // removed once published
+            let checkout = match &from_git {
+                Some(reference) => {
+                    let dir = match &dir {
+                        Some(dir) => dir.clone(),
+                        None => std::env::current_dir()?,
+                    };
+                    Some(git::archive(&dir, reference)?)
+                }
+                None => None,
+            };
+            let source = checkout
+                .as_ref()
+                .map(|checkout| checkout.path().into())
+                .or(dir);
+            let name = registry.publish(name.as_deref(), source, force)?;
+            if print_name {
+                println!("{name}");
+            } else if args.quiet {
+                println!("{}", registry.url(&name));
+            }
+            if !args.dry_run {
+                save_name(&name)?;
+                if open {
+                    open_in_browser(&registry.url(&name));
+                }
+            }
+        }
+        Some(Cmd::Delete { name, yes }) => {
+            if !yes && !args.dry_run && std::io::stdin().is_terminal() {
+                print!(
+                    "This deletes {}\nType \"{name}\" to confirm: ",
+                    registry.location(&name)
+                );
+                std::io::stdout().flush()?;
+                if !confirm(&name, &mut std::io::stdin().lock())? {
+                    anyhow::bail!("deletion aborted");
+                }
+            }
             registry.delete(&name)?;
         }
-        Some(Cmd::List) => registry.list()?,
+        Some(Cmd::Rename { old, new }) => {
+            registry.rename(&old, &new)?;
+            // keep .zou pointing at the renamed project
+            if !args.dry_run && std::env::var("ZOU_NAME").is_ok_and(|name| name == old) {
+                save_name(&new)?;
+            }
+        }
+        Some(Cmd::Logs { log_source, lines }) => registry.logs(&log_source, lines)?,
+        Some(Cmd::Status { name }) => {
+            registry::validate_name(&name)?;
+            let live = match &args.public_base {
+                Some(_) => status::report_url(&registry.url(&name)),
+                None => status::report(&name, host),
+            };
+            if !live {
+                anyhow::bail!("\"{name}\" is not live");
+            }
+        }
+        Some(Cmd::Config { .. } | Cmd::Completions { .. }) => unreachable!("handled first"),
+        Some(Cmd::List {
+            format: Format::Text,
+            plain,
+        }) => registry.list(plain)?,
+        Some(Cmd::List {
+            format: Format::Json,
+            ..
+        }) => {
+            let sites = registry.list_sites()?;
+            println!("{}", serde_json::to_string_pretty(&sites)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Validates a rate for rsync `--bwlimit`: a number of KiB, or of the given `k`, `m` or `g` unit
+fn parse_bwlimit(value: &str) -> Result<String, String> {
+    let number = value
+        .strip_suffix(['k', 'K', 'm', 'M', 'g', 'G'])
+        .unwrap_or(value);
+    match number.parse::<f64>() {
+        Ok(number) if number > 0.0 && number.is_finite() => Ok(value.to_string()),
+        _ => Err("expected a positive rate like 500k or 2m".to_string()),
+    }
+}
+
+/// Checks that the settings needed by the command to reach the registry are given
+fn check_registry_args(args: &Args) -> anyhow::Result<()> {
+    if args.host.is_none() {
+        anyhow::bail!("this command needs the registry, --host is required");
+    }
+    let over_ssh = match &args.cmd {
+        Some(Cmd::Delete { .. } | Cmd::Rename { .. } | Cmd::List { .. }) => true,
+        // publishing runs over HTTP with --upload-url
+        None | Some(Cmd::Publish { .. }) => args.upload_url.is_none(),
+        _ => false,
+    };
+    if over_ssh && (args.user.is_none() || args.upload_dir.is_none()) {
+        anyhow::bail!("this command runs over SSH, --user and --upload-dir are required");
+    }
+    if matches!(args.cmd, Some(Cmd::Logs { .. })) && args.user.is_none() {
+        anyhow::bail!("this command runs over SSH, --user is required");
+    }
+    Ok(())
+}
+
+fn run_config(cmd: &ConfigCmd) -> anyhow::Result<()> {
+    match *cmd {
+        ConfigCmd::Init { global } => {
+            let path = if global {
+                dotzou::global_path().context("unable to find the home directory")?
+            } else {
+                std::env::current_dir()?.join(dotzou::FILE_NAME)
+            };
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("unable to create {parent:?}"))?;
+            }
+            init_config(&path, &mut std::io::stdin().lock(), &mut std::io::stdout())?;
+            println!("Wrote {}", path.display());
+            Ok(())
+        }
     }
+}
+
+/// Prompts for each of [`CONFIG_VARS`] and writes the answers to the config file at `path`
+///
+/// An empty answer keeps the current value, which is only replaced once confirmed.
+fn init_config(
+    path: &Path,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> anyhow::Result<()> {
+    for (var, question, required) in CONFIG_VARS {
+        let current = dotzou::get_var(path, var)?;
+        let value = loop {
+            match &current {
+                Some(current) => write!(output, "{question} [{current}]: ")?,
+                None => write!(output, "{question}: ")?,
+            }
+            output.flush()?;
+
+            let answer = read_answer(input)?;
+            if answer.is_empty() {
+                if required && current.is_none() {
+                    continue;
+                }
+                break None;
+            }
+            if var == "ZOU_SSH_PORT" && answer.parse::<u16>().is_err() {
+                writeln!(output, "expected a port number")?;
+                continue;
+            }
+            break Some(answer);
+        };
 
+        let Some(value) = value else { continue };
+        if let Some(current) = current.filter(|current| *current != value) {
+            write!(output, "Overwrite {var}={current} with {value}? [y/N]: ")?;
+            output.flush()?;
+            if !read_answer(input)?.eq_ignore_ascii_case("y") {
+                continue;
+            }
+        }
+        dotzou::set_var(path, var, &value)?;
+    }
     Ok(())
 }
 
-fn load_dotenv() {
+/// Reads a trimmed line from `input`, failing when it has nothing more to read
+fn read_answer(input: &mut impl BufRead) -> anyhow::Result<String> {
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        anyhow::bail!("config init aborted");
+    }
+    Ok(answer.trim().to_string())
+}
+
+/// Validates a `--public-base`: an HTTP(S) URL with a `{name}` placeholder
+fn parse_public_base(value: &str) -> Result<String, String> {
+    if !value.starts_with("http://") && !value.starts_with("https://") {
+        return Err("expected an http:// or https:// URL".to_string());
+    }
+    if !value.contains("{name}") {
+        return Err("expected a {name} placeholder".to_string());
+    }
+    Ok(value.to_string())
+}
+
+fn print_completions(shell: Shell) {
+    clap_complete::generate(shell, &mut Args::command(), "zou", &mut std::io::stdout());
+}
+
+/// Opens `url` in the default browser, unless there is none to open like on CI
+fn open_in_browser(url: &str) {
+    if std::env::var_os("CI").is_some() {
+        return;
+    }
+    if let Err(err) = webbrowser::open(url) {
+        eprintln!("unable to open {url} in a browser: {err}");
+    }
+}
+
+/// Reads a line from `input`, confirming when it is `expected`
+fn confirm(expected: &str, input: &mut impl BufRead) -> std::io::Result<bool> {
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(answer.trim() == expected)
+}
+
+/// Saves `ZOU_NAME` in `$PWD/.zou` so that the next commands target the same project
+fn save_name(name: &str) -> anyhow::Result<()> {
+    let local_config = std::env::current_dir()?.join(dotzou::FILE_NAME);
+    dotzou::set_var(&local_config, "ZOU_NAME", name)
+}
+
+fn load_dotenv() -> anyhow::Result<()> {
     // try to load from $PWD/.zou
     if let Ok(cwd) = std::env::current_dir() {
-        let local_config = cwd.join(".zou");
+        let local_config = cwd.join(dotzou::FILE_NAME);
         dotenvy::from_path(local_config).ok();
     }
-    // try to load from $HOME/.config/zou/config
-    if let Some(home_dir) = home::home_dir() {
-        let global_config = home_dir.join(".config").join("zou").join("config");
-        dotenvy::from_path(global_config).ok();
+    // try to load from $HOME/.config/zou/config, or only its flat part and the chosen profile
+    if let Some(global_config) = dotzou::global_path() {
+        match profile_arg().or_else(|| std::env::var("ZOU_PROFILE").ok()) {
+            Some(profile) => {
+                let content = std::fs::read_to_string(&global_config)
+                    .with_context(|| format!("unable to read {global_config:?}"))?;
+                let vars = dotzou::profile_vars(&content, &profile)
+                    .with_context(|| format!("invalid {global_config:?}"))?;
+                for (key, value) in unset_vars(vars, |key| std::env::var_os(key).is_some()) {
+                    std::env::set_var(key, value);
+                }
+            }
+            None => {
+                dotenvy::from_path(global_config).ok();
+            }
+        }
     }
     dotenvy::dotenv_override().ok();
+    Ok(())
+}
+
+/// Variables of `vars` that `is_set` does not report as already set, the last value of each winning
+///
+/// `vars` being the flat ones followed by those of the profile, the profile overrides the defaults.
+fn unset_vars(vars: Vec<(String, String)>, is_set: impl Fn(&str) -> bool) -> Vec<(String, String)> {
+    let mut unset: Vec<(String, String)> = Vec::new();
+    for (key, value) in vars {
+        if is_set(&key) {
+            continue;
+        }
+        match unset.iter_mut().find(|(unset_key, _)| *unset_key == key) {
+            Some((_, unset_value)) => *unset_value = value,
+            None => unset.push((key, value)),
+        }
+    }
+    unset
+}
+
+/// Value of `--profile`, looked up ahead of parsing the arguments whose defaults it selects
+fn profile_arg() -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == "--profile" {
+            return args.next();
+        }
+        if let Some(profile) = arg.strip_prefix("--profile=") {
+            return Some(profile.to_string());
+        }
+    }
+    None
+}
+
+#[test]
+fn test_confirm() {
+    assert!(confirm("my-site", &mut "my-site\n".as_bytes()).unwrap());
+    assert!(!confirm("my-site", &mut "y\n".as_bytes()).unwrap());
+    assert!(!confirm("my-site", &mut "".as_bytes()).unwrap());
+}
+
+#[test]
+fn test_parse_bwlimit() {
+    assert_eq!(Ok("2m".to_string()), parse_bwlimit("2m"));
+    assert_eq!(Ok("500K".to_string()), parse_bwlimit("500K"));
+    assert_eq!(Ok("1.5g".to_string()), parse_bwlimit("1.5g"));
+    assert_eq!(Ok("800".to_string()), parse_bwlimit("800"));
+    assert!(parse_bwlimit("fast").is_err());
+    assert!(parse_bwlimit("0m").is_err());
+    assert!(parse_bwlimit("m").is_err());
+}
+
+#[test]
+fn test_init_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(dotzou::FILE_NAME);
+    std::fs::write(&path, "ZOU_NAME=my-site\nZOU_HOST=old.example.com\n").unwrap();
+
+    // the empty user is asked again, the host is kept and the bad port asked again
+    let answers = "\nzou\nnew.example.com\nn\n/srv/zou\nssh\n2222\n\n";
+    let mut output = Vec::new();
+    init_config(&path, &mut answers.as_bytes(), &mut output).unwrap();
+    assert_eq!(
+        "ZOU_NAME=my-site\nZOU_HOST=old.example.com\nZOU_USER=zou\nZOU_UPLOAD_DIR=/srv/zou\nZOU_SSH_PORT=2222\n",
+        std::fs::read_to_string(&path).unwrap()
+    );
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("SSH host [old.example.com]: "));
+    assert!(output.contains("Overwrite ZOU_HOST=old.example.com with new.example.com? [y/N]: "));
+    assert!(output.contains("expected a port number"));
+
+    let answers = "\nnew.example.com\ny\n\n\n\n";
+    init_config(&path, &mut answers.as_bytes(), &mut Vec::new()).unwrap();
+    assert_eq!(
+        Some("new.example.com".to_string()),
+        dotzou::get_var(&path, "ZOU_HOST").unwrap()
+    );
+
+    assert!(init_config(&path, &mut "".as_bytes(), &mut Vec::new()).is_err());
+}
+
+#[test]
+fn test_parse_public_base() {
+    let base = "https://{name}.sites.example.com";
+    assert_eq!(Ok(base.to_string()), parse_public_base(base));
+    assert!(parse_public_base("http://example.com/{name}/").is_ok());
+    assert!(parse_public_base("https://sites.example.com").is_err());
+    assert!(parse_public_base("{name}.example.com").is_err());
+}
+
+#[test]
+fn test_check_registry_args() {
+    let args = |argv: &[&str]| Args::try_parse_from([&["zou"], argv].concat()).unwrap();
+    let ssh = [
+        "--host",
+        "zou.example.com",
+        "--user",
+        "zou",
+        "--upload-dir",
+        "/srv",
+    ];
+
+    // parsed without any of the registry settings
+    let config = args(&["--debug", "config", "init"]);
+    assert!(matches!(config.cmd, Some(Cmd::Config { .. })));
+    assert!(check_registry_args(&config).is_err());
+
+    assert!(check_registry_args(&args(&ssh)).is_ok());
+    assert!(check_registry_args(&args(&["--host", "zou.example.com", "list"])).is_err());
+    assert!(check_registry_args(&args(&["--host", "zou.example.com", "status", "blog"])).is_ok());
+    assert!(check_registry_args(&args(&["--user", "zou", "--upload-dir", "/srv"])).is_err());
+    let http = [
+        "--host",
+        "zou.example.com",
+        "--upload-url",
+        "https://zou.example.com/__upload",
+        "--upload-token",
+        "s3cr3t",
+        "publish",
+    ];
+    assert!(check_registry_args(&args(&http)).is_ok());
+    assert!(check_registry_args(&args(&[&ssh[..2], &["logs"]].concat())).is_err());
+}
+
+#[test]
+fn test_unset_vars() {
+    let config = "ZOU_USER=zou\nZOU_HOST=example.com\nZOU_SSH_PORT=22\n\n[prod]\nZOU_USER=deploy\nZOU_SSH_PORT=2222\n";
+    let vars = dotzou::profile_vars(config, "prod").unwrap();
+    let var = |key: &str, value: &str| (key.to_string(), value.to_string());
+
+    // the profile overrides the flat defaults, never what the environment already sets
+    assert_eq!(
+        vec![var("ZOU_USER", "deploy"), var("ZOU_HOST", "example.com")],
+        unset_vars(vars, |key| key == "ZOU_SSH_PORT")
+    );
 }
@@ -0,0 +1,86 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use serde::Deserialize;
+
+use crate::registry::validate_name;
+
+/// Name of the manifest listing the sites of a monorepo
+pub const FILE_NAME: &str = "zou.toml";
+
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    site: Vec<Site>,
+}
+
+/// Directory published as the site `name`
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct Site {
+    pub dir: PathBuf,
+    pub name: String,
+}
+
+/// Loads the `[[site]]` tables of a manifest, relative directories being relative to the file
+pub fn load(path: &Path) -> anyhow::Result<Vec<Site>> {
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("unable to read {path:?}"))?;
+    let manifest: Manifest =
+        toml::from_str(&content).with_context(|| format!("invalid manifest {path:?}"))?;
+    if manifest.site.is_empty() {
+        bail!("no [[site]] in {path:?}");
+    }
+
+    let base = path.parent().unwrap_or(Path::new("."));
+    let mut names = HashSet::new();
+    let mut sites = Vec::new();
+    for site in manifest.site {
+        validate_name(&site.name).with_context(|| format!("invalid site in {path:?}"))?;
+        if !names.insert(site.name.clone()) {
+            bail!("\"{}\" is published twice by {path:?}", site.name);
+        }
+        sites.push(Site {
+            dir: base.join(site.dir),
+            name: site.name,
+        });
+    }
+    Ok(sites)
+}
+
+#[test]
+fn test_load() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join(FILE_NAME);
+    std::fs::write(
+        &file,
+        "[[site]]\ndir = \"docs/dist\"\nname = \"docs\"\n\n[[site]]\ndir = \"/srv/blog\"\nname = \"blog\"\n",
+    )
+    .unwrap();
+    assert_eq!(
+        vec![
+            Site {
+                dir: dir.path().join("docs/dist"),
+                name: "docs".to_string()
+            },
+            Site {
+                dir: "/srv/blog".into(),
+                name: "blog".to_string()
+            },
+        ],
+        load(&file).unwrap()
+    );
+
+    std::fs::write(
+        &file,
+        "[[site]]\ndir = \"a\"\nname = \"a\"\n[[site]]\ndir = \"b\"\nname = \"a\"\n",
+    )
+    .unwrap();
+    assert!(load(&file).is_err());
+
+    std::fs::write(&file, "[[site]]\ndir = \"docs\"\nname = \"Docs\"\n").unwrap();
+    assert!(load(&file).is_err());
+
+    std::fs::write(&file, "").unwrap();
+    assert!(load(&file).is_err());
+}
@@ -1,92 +1,1182 @@
-use std::{path::PathBuf, process::Command};
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
 
-use anyhow::bail;
+use anyhow::{bail, Context};
+use serde::Serialize;
+
+use crate::manifest;
+use crate::runner::{self, Runner};
+use crate::upload::{self, HttpUpload};
+
+/// Name of the file listing gitignore-style patterns left out of publish
+pub const IGNORE_FILE: &str = ".zouignore";
+
+/// Published site, `size` in bytes of all its files and `modified` in seconds since the Unix epoch
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Site {
+    pub name: String,
+    pub size: u64,
+    pub modified: u64,
+}
+
+/// How publish names the sites it is not given a name for
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+pub enum NameStyle {
+    /// `adjective-noun-1234`
+    #[default]
+    Numbered,
+    /// `adjective-noun`
+    Plain,
+    /// random UUID
+    Uuid,
+}
 
 pub struct Registry {
     user: String,
     host: String,
     root_dir: PathBuf,
+    port: u16,
     pub debug: bool,
+    pub dry_run: bool,
+    /// whether the `✔`/`✘` lines reporting what was done are left out
+    pub quiet: bool,
+    /// whether the sites are browsed over HTTPS
+    pub https: bool,
+    /// public URL of the sites, `{name}` standing for the name of each
+    pub public_base: Option<String>,
+    /// whether rsync shows a single progress line of the whole transfer
+    pub progress: bool,
+    pub identity: Option<PathBuf>,
+    pub excludes: Vec<String>,
+    /// rsync `--bwlimit` rate, like `500k` or `2m`
+    pub bwlimit: Option<String>,
+    /// whether publish refuses to merge into an existing site without `force`
+    pub check_exists: bool,
+    /// name the project was last published to, saved in `.zou`, which publish never refuses
+    pub saved_name: Option<String>,
+    /// whether publish first checks that the upload directory exists and is writable
+    pub preflight: bool,
+    pub name_style: NameStyle,
+    /// words of the generated names instead of the default adjectives and nouns
+    pub wordlist: Vec<String>,
+    /// how many times commands failing on the network are run again
+    pub retries: u32,
+    retry_delay: Duration,
+    pub runner: Box<dyn Runner>,
+    /// publishes through this zoubida endpoint instead of rsync
+    pub upload: Option<HttpUpload>,
 }
 
 impl Registry {
-    pub fn new(user: &str, host: &str, root_dir: impl Into<PathBuf>) -> Self {
+    pub fn new(user: &str, host: &str, port: u16, root_dir: impl Into<PathBuf>) -> Self {
         Self {
             user: user.to_string(),
             host: host.to_string(),
             root_dir: root_dir.into(),
+            port,
             debug: false,
+            dry_run: false,
+            quiet: false,
+            https: false,
+            public_base: None,
+            progress: false,
+            identity: None,
+            excludes: Vec::new(),
+            bwlimit: None,
+            check_exists: true,
+            saved_name: None,
+            preflight: true,
+            name_style: NameStyle::default(),
+            wordlist: Vec::new(),
+            retries: 0,
+            retry_delay: Duration::from_secs(1),
+            runner: Box::new(runner::System),
+            upload: None,
         }
     }
 
-    pub fn publish(&self, name: Option<&str>, source: Option<PathBuf>) -> anyhow::Result<String> {
+    /// Syncs `source` to the site `name`, with `force` removing remote files that are gone locally
+    ///
+    /// Uploads over HTTP always replace the whole site.
+    pub fn publish(
+        &self,
+        name: Option<&str>,
+        source: Option<PathBuf>,
+        force: bool,
+    ) -> anyhow::Result<String> {
         let source = source.unwrap_or(std::env::current_dir()?);
         if !source.exists() || !source.is_dir() {
             bail!("file does not exist or is not a directory");
         }
 
-        let source = format!("{}/", source.to_string_lossy());
-        let user = &self.user;
-        let host = &self.host;
-        let mut path = self.root_dir.clone();
-        let name = name.map_or(gen_name(), Into::into);
-        path.push(&name);
-        let path = path.to_string_lossy();
-        let target = format!("{user}@{host}:{path}");
-
-        let mut rsync = Command::new("rsync");
-        rsync.args(["-zr", "--exclude", ".zou", &source, &target]);
-        if self.debug {
-            rsync.arg("--progress");
+        if self.preflight && self.upload.is_none() {
+            let root_dir = self.root_dir.to_string_lossy();
+            let mut ssh = self.ssh_command(&preflight_script(&root_dir));
+            self.run(
+                &mut ssh,
+                &format!("unable to publish to {}:{root_dir}", self.target()),
+            )?;
+        }
+        // over HTTP, the registry itself refuses to replace an existing site
+        let replace = force
+            || !self.check_exists
+            || name.is_some_and(|name| self.saved_name.as_deref() == Some(name));
+        if let Some(name) = name {
+            validate_name(name)?;
+            if !replace && self.upload.is_none() && self.exists(name)? {
+                bail!("\"{name}\" already exists, use --force to replace its content");
+            }
+        }
+        let name = name.map_or_else(|| gen_name(self.name_style, &self.wordlist), Into::into);
+        if let Some(upload) = &self.upload {
+            let zouignore = read_zouignore(&source)?;
+            let archive = upload::tarball(&source, &patterns(&zouignore, &self.excludes))?;
+            if self.dry_run {
+                println!(
+                    "dry run: PUT {}/{name} ({} bytes)",
+                    upload.url.trim_end_matches('/'),
+                    archive.len()
+                );
+            } else {
+                upload.put(&name, &archive, replace)?;
+            }
+            if !self.quiet {
+                println!("✔ {}", self.url(&name));
+            }
+            return Ok(name);
         }
-        let status = rsync.status()?;
 
-        if !status.success() {
-            bail!("unable to sync");
+        let mut rsync = self.rsync_command(&source, &name, force)?;
+        if self.dry_run {
+            println!("dry run: {} ({name})", display(&rsync));
+        }
+        let output = runner::retry(
+            self.retries,
+            self.retry_delay,
+            || self.runner.tee(&mut rsync, STATS_START),
+            |output| runner::is_transient(&output.status),
+        )
+        .map_err(|error| runner::spawn_error(&rsync, error))?;
+        if !output.status.success() {
+            return Err(failure("unable to sync", &output));
+        }
+        eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        if self.quiet {
+            return Ok(name);
+        }
+        if let Some((files, bytes)) = parse_stats(&String::from_utf8_lossy(&output.stdout)) {
+            let plural = if files == 1 { "" } else { "s" };
+            println!("uploaded {files} file{plural}, {}", human_size(bytes));
         }
 
-        println!("✔ http://{name}.{host}", host = self.host);
+        println!("✔ {}", self.url(&name));
         Ok(name)
     }
 
-    pub fn delete(&self, name: &str) -> anyhow::Result<()> {
-        let Self { user, host, .. } = self;
+    /// Publishes every site with up to `jobs` of them at once, returning the result of each
+    pub fn publish_all(
+        &self,
+        sites: &[manifest::Site],
+        force: bool,
+        jobs: usize,
+    ) -> Vec<anyhow::Result<String>> {
+        let next = AtomicUsize::new(0);
+        let mut results: Vec<_> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..jobs.clamp(1, sites.len().max(1)))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(site) = sites.get(index) else {
+                                return done;
+                            };
+                            let published =
+                                self.publish(Some(&site.name), Some(site.dir.clone()), force);
+                            done.push((index, published));
+                        }
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("publish worker panicked"))
+                .collect()
+        });
+        results.sort_by_key(|(index, _)| *index);
+        results
+            .into_iter()
+            .map(|(_, published)| published)
+            .collect()
+    }
 
-        let mut path = self.root_dir.clone();
-        path.push(name);
+    pub fn delete(&self, name: &str) -> anyhow::Result<()> {
+        validate_name(name)?;
+        let path = self.site_path(name);
 
-        let status = Command::new("ssh")
-            .arg(format!("{user}@{host}"))
-            .arg(format!("rm -rf {}", path.to_string_lossy()))
-            .status()?;
+        let mut ssh = self.ssh_command(&format!("rm -rf {}", quote(&path)));
+        self.run(&mut ssh, "unable to delete")?;
 
-        if !status.success() {
-            bail!("unable to delete");
+        if !self.dry_run && !self.quiet {
+            println!("✘ deleted \"{name}\"");
         }
+        Ok(())
+    }
 
-        println!("✘ deleted \"{name}\"");
+    /// Renames the site `old` to `new`, failing when `new` already exists
+    pub fn rename(&self, old: &str, new: &str) -> anyhow::Result<()> {
+        validate_name(old)?;
+        validate_name(new)?;
+        let mut ssh = self.ssh_command(&rename_script(&self.site_path(old), &self.site_path(new)));
+        self.run(
+            &mut ssh,
+            &format!("unable to rename \"{old}\" to \"{new}\""),
+        )?;
+
+        if !self.dry_run && !self.quiet {
+            println!("✔ {}", self.url(new));
+        }
         Ok(())
     }
 
-    pub fn list(&self) -> anyhow::Result<()> {
-        let Self { user, host, .. } = self;
+    /// Prints the URL of every site, or only their names when `plain` is set
+    pub fn list(&self, plain: bool) -> anyhow::Result<()> {
         let path = self.root_dir.to_string_lossy();
 
-        let status = Command::new("ssh")
-            .arg(format!("{user}@{host}"))
-            .arg(format!("ls {path}"))
-            .status()?;
+        let mut ssh = self.ssh_command(&format!("ls {}", quote(&path)));
+        let Some(listing) = self.output(&mut ssh).context("unable to list")? else {
+            return Ok(());
+        };
+        for name in listing.lines().filter(|name| !name.is_empty()) {
+            if plain {
+                println!("{name}");
+            } else {
+                println!("{}", self.url(name));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sites of the registry, for scripts
+    pub fn list_sites(&self) -> anyhow::Result<Vec<Site>> {
+        let mut ssh = self.ssh_command(&listing_script(&self.root_dir.to_string_lossy()));
+        let listing = self.output(&mut ssh).context("unable to list")?;
+        Ok(listing.as_deref().map(parse_listing).unwrap_or_default())
+    }
+
+    /// Whether the site `name` exists on the registry, never in dry-run mode
+    fn exists(&self, name: &str) -> anyhow::Result<bool> {
+        let mut ssh = self.ssh_command(&format!("test -d {}", quote(&self.site_path(name))));
+        if self.dry_run {
+            println!("dry run: {}", display(&ssh));
+            return Ok(false);
+        }
+        let output = self.retried(&mut ssh)?;
+        match output.status.code() {
+            Some(0) => Ok(true),
+            Some(1) => Ok(false),
+            _ => Err(failure(
+                &format!("unable to check whether \"{name}\" exists"),
+                &output,
+            )),
+        }
+    }
+
+    /// Streams the last `lines` of the registry's logs and then the new ones, until interrupted
+    ///
+    /// `source` is the path of a log file, or else the name of a journald unit.
+    pub fn logs(&self, source: &str, lines: u32) -> anyhow::Result<()> {
+        // a pseudo-tty forwards Ctrl-C to the remote command, which then stops
+        let mut ssh = Command::new("ssh");
+        ssh.arg("-t")
+            .args(self.ssh_options())
+            .arg(self.target())
+            .arg(logs_command(source, lines));
+        if self.dry_run {
+            println!("dry run: {}", display(&ssh));
+            return Ok(());
+        }
+
+        ssh.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+        let output = self
+            .runner
+            .output(&mut ssh)
+            .map_err(|error| runner::spawn_error(&ssh, error))?;
+        match output.status.code() {
+            // 130 when interrupted
+            Some(0 | 130) | None => Ok(()),
+            Some(255) => bail!("unable to connect to {}", self.target()),
+            Some(_) => bail!("unable to read the logs of {source}"),
+        }
+    }
+
+    /// Browsable URL of the site `name`
+    pub fn url(&self, name: &str) -> String {
+        if let Some(public_base) = &self.public_base {
+            return public_base.replace("{name}", name);
+        }
+        let scheme = if self.https { "https" } else { "http" };
+        format!("{scheme}://{name}.{}", self.host)
+    }
+
+    /// Remote location of the site `name`, as `user@host:path`
+    pub fn location(&self, name: &str) -> String {
+        format!("{}:{}", self.target(), self.site_path(name))
+    }
+
+    fn site_path(&self, name: &str) -> String {
+        self.root_dir.join(name).to_string_lossy().into_owned()
+    }
+
+    fn target(&self) -> String {
+        format!("{}@{}", self.user, self.host)
+    }
+
+    fn rsync_command(&self, source: &Path, name: &str, force: bool) -> anyhow::Result<Command> {
+        let zouignore = read_zouignore(source)?;
+
+        let source = format!("{}/", source.to_string_lossy());
+        let mut path = self.root_dir.clone();
+        path.push(name);
+        let target = format!("{}:{}", self.target(), path.to_string_lossy());
+
+        let mut rsync = Command::new("rsync");
+        rsync.args(["-zr", "--exclude", ".zou", "--exclude", IGNORE_FILE]);
+        let ssh_options = self.ssh_options();
+        if !ssh_options.is_empty() {
+            let ssh_options: Vec<_> = ssh_options.iter().map(|opt| quote(opt)).collect();
+            rsync
+                .arg("-e")
+                .arg(format!("ssh {}", ssh_options.join(" ")));
+        }
+        rsync.args(filter_args(&zouignore, &self.excludes));
+        if force {
+            rsync.arg("--delete");
+        }
+        if let Some(bwlimit) = &self.bwlimit {
+            rsync.arg(format!("--bwlimit={bwlimit}"));
+        }
+        rsync.args([&source, &target]);
+        if self.progress && !self.dry_run {
+            rsync.arg("--info=progress2");
+        } else if self.debug {
+            rsync.arg("--progress");
+        }
+        if self.dry_run {
+            rsync.args(["--dry-run", "--itemize-changes"]);
+        } else {
+            rsync.arg("--stats");
+        }
+        Ok(rsync)
+    }
+
+    fn ssh_command(&self, remote: &str) -> Command {
+        let mut ssh = Command::new("ssh");
+        ssh.args(self.ssh_options()).arg(self.target()).arg(remote);
+        ssh
+    }
+
+    /// Options given to ssh, directly or through rsync
+    fn ssh_options(&self) -> Vec<String> {
+        let mut options = Vec::new();
+        if self.port != 22 {
+            options.extend(["-p".to_string(), self.port.to_string()]);
+        }
+        if let Some(identity) = &self.identity {
+            options.extend(["-i".to_string(), identity.to_string_lossy().into_owned()]);
+        }
+        options
+    }
 
-        if !status.success() {
-            bail!("unable to delete");
+    /// Runs `cmd`, or only prints it in dry-run mode
+    fn run(&self, cmd: &mut Command, message: &str) -> anyhow::Result<()> {
+        if self.dry_run {
+            println!("dry run: {}", display(cmd));
+            return Ok(());
         }
+        self.check(cmd, message)
+    }
 
+    /// Runs `cmd` with its stdout shown, failing with `message` and its stderr when it fails
+    fn check(&self, cmd: &mut Command, message: &str) -> anyhow::Result<()> {
+        cmd.stdout(Stdio::inherit());
+        let output = self.retried(cmd)?;
+        if !output.status.success() {
+            return Err(failure(message, &output));
+        }
+        // warnings of a successful command
+        eprint!("{}", String::from_utf8_lossy(&output.stderr));
         Ok(())
     }
+
+    fn retried(&self, cmd: &mut Command) -> anyhow::Result<Output> {
+        runner::retry(
+            self.retries,
+            self.retry_delay,
+            || self.runner.output(cmd),
+            |output| runner::is_transient(&output.status),
+        )
+        .map_err(|error| runner::spawn_error(cmd, error))
+    }
+
+    /// Captures the stdout of `cmd`, `None` in dry-run mode where it is only printed
+    fn output(&self, cmd: &mut Command) -> anyhow::Result<Option<String>> {
+        if self.dry_run {
+            println!("dry run: {}", display(cmd));
+            return Ok(None);
+        }
+        let output = self.retried(cmd)?;
+        if !output.status.success() {
+            bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()))
+    }
+}
+
+/// Error of a failed command, `message` followed by what it printed on stderr
+fn failure(message: &str, output: &Output) -> anyhow::Error {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match stderr.trim() {
+        "" => anyhow::anyhow!("{message} ({})", output.status),
+        stderr => anyhow::anyhow!("{message}: {stderr}"),
+    }
+}
+
+/// First line of the statistics printed by rsync `--stats`
+const STATS_START: &str = "Number of files:";
+
+/// Number of files and bytes transferred, from the output of rsync `--stats`
+fn parse_stats(output: &str) -> Option<(u64, u64)> {
+    let stat = |name: &str| {
+        let line = output.lines().find_map(|line| line.strip_prefix(name))?;
+        let number = line.trim_start_matches(':').split_whitespace().next()?;
+        // thousands are separated depending on the locale
+        let digits: String = number.chars().filter(char::is_ascii_digit).collect();
+        digits.parse().ok()
+    };
+    Some((
+        stat("Number of regular files transferred")?,
+        stat("Total transferred file size")?,
+    ))
+}
+
+/// `bytes` in decimal units, like `3.2 MB`
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["kB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while size >= 999.95 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Remote command following the log file or journald unit `source`
+fn logs_command(source: &str, lines: u32) -> String {
+    let source = quote(source);
+    if source.starts_with('/') || source.starts_with("'/") {
+        format!("tail -n {lines} -F {source}")
+    } else {
+        format!("journalctl -u {source} -n {lines} -f")
+    }
+}
+
+/// Remote shell script failing with the reason when `root_dir` cannot receive sites
+fn preflight_script(root_dir: &str) -> String {
+    let root_dir = quote(root_dir);
+    format!(
+        "test -d {root_dir} || {{ printf '%s does not exist\\n' {root_dir} >&2; exit 1; }}; \
+         test -w {root_dir} || {{ printf '%s is not writable\\n' {root_dir} >&2; exit 1; }}"
+    )
+}
+
+/// Remote shell script moving `old` to `new` unless `new` exists
+fn rename_script(old: &str, new: &str) -> String {
+    let (old, new) = (quote(old), quote(new));
+    format!(
+        "test ! -e {new} || {{ printf '%s already exists\\n' {new} >&2; exit 1; }} && mv {old} {new}"
+    )
+}
+
+/// Remote shell script printing a `<name>\t<size>\t<mtime>` line per site of `root_dir`
+///
+/// The size is the one of the whole site, in bytes.
+fn listing_script(root_dir: &str) -> String {
+    format!(
+        "cd {} && for site in *; do if [ -e \"$site\" ]; then \
+         printf '%s\\t%s\\t%s\\n' \"$site\" \"$(du -sb -- \"$site\" | cut -f1)\" \"$(stat -c %Y -- \"$site\")\"; \
+         fi; done",
+        quote(root_dir)
+    )
+}
+
+/// Parses the `<name>\t<size>\t<mtime>` lines printed by [`listing_script`]
+fn parse_listing(output: &str) -> Vec<Site> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let name = fields.next()?.to_string();
+            let size = fields.next()?.parse().ok()?;
+            let modified = fields.next()?.split('.').next()?.parse().ok()?;
+            Some(Site {
+                name,
+                size,
+                modified,
+            })
+        })
+        .collect()
+}
+
+/// Content of the `.zouignore` of `source`, empty when it has none
+fn read_zouignore(source: &Path) -> anyhow::Result<String> {
+    let ignore_file = source.join(IGNORE_FILE);
+    match std::fs::read_to_string(&ignore_file) {
+        Ok(content) => Ok(content),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(err) => Err(err).with_context(|| format!("unable to read {ignore_file:?}")),
+    }
+}
+
+/// Patterns of a `.zouignore` followed by the ad-hoc `excludes`
+fn patterns<'a>(zouignore: &'a str, excludes: &'a [String]) -> Vec<&'a str> {
+    zouignore
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .chain(excludes.iter().map(String::as_str))
+        .collect()
+}
+
+/// rsync filters for the patterns of a `.zouignore` and the ad-hoc `excludes`
+///
+/// The last matching gitignore pattern wins whereas rsync applies the first matching rule, so the
+/// rules are given in reverse order, `!` negations becoming includes.
+fn filter_args(zouignore: &str, excludes: &[String]) -> Vec<String> {
+    let mut args: Vec<String> = patterns(zouignore, excludes)
+        .into_iter()
+        .map(|pattern| match pattern.strip_prefix('!') {
+            Some(pattern) => format!("--include={pattern}"),
+            None => format!("--exclude={pattern}"),
+        })
+        .collect();
+    args.reverse();
+    args
+}
+
+/// Quotes `arg` for the remote shell, unless it is only made of characters it never interprets
+fn quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "/._-=:,@+%".contains(c);
+    if arg.is_empty() || !arg.chars().all(plain) {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    } else {
+        arg.to_string()
+    }
+}
+
+/// Command line of `cmd`, for display only
+fn display(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| arg.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
-fn gen_name() -> String {
-    names::Generator::with_naming(names::Name::Numbered)
+/// Checks that `name` is usable both as a directory and as a DNS label: 1 to 63 lowercase
+/// alphanumerics and hyphens, not starting nor ending with a hyphen
+pub fn validate_name(name: &str) -> anyhow::Result<()> {
+    if name.is_empty() || name.len() > 63 {
+        bail!("invalid name \"{name}\": must be 1 to 63 characters long");
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !c.is_ascii_lowercase() && !c.is_ascii_digit() && *c != '-')
+    {
+        bail!("invalid name \"{name}\": {c:?} is not a lowercase letter, a digit or '-'");
+    }
+    if name.starts_with('-') || name.ends_with('-') {
+        bail!("invalid name \"{name}\": must not start or end with '-'");
+    }
+    Ok(())
+}
+
+/// Reads the words of a `--wordlist`, one per line, each usable in a name
+pub fn load_wordlist(path: &Path) -> anyhow::Result<Vec<String>> {
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("unable to read {path:?}"))?;
+    let words: Vec<String> = content
+        .lines()
+        .map(str::trim)
+        .filter(|word| !word.is_empty() && !word.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+    for word in &words {
+        validate_name(word).with_context(|| format!("invalid word in {path:?}"))?;
+    }
+    if words.is_empty() {
+        bail!("no words in {path:?}");
+    }
+    Ok(words)
+}
+
+fn gen_name(style: NameStyle, wordlist: &[String]) -> String {
+    let naming = match style {
+        NameStyle::Numbered => names::Name::Numbered,
+        NameStyle::Plain => names::Name::Plain,
+        NameStyle::Uuid => return gen_uuid(),
+    };
+    if wordlist.is_empty() {
+        return names::Generator::with_naming(naming).next().unwrap();
+    }
+    let words: Vec<&str> = wordlist.iter().map(String::as_str).collect();
+    names::Generator::new(&words, &words, naming)
         .next()
         .unwrap()
 }
+
+/// Random (version 4) UUID
+fn gen_uuid() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Output of a command exiting with `code` after printing `stderr`
+#[cfg(test)]
+fn exited(code: i32, stderr: &[u8]) -> Output {
+    use std::os::unix::process::ExitStatusExt;
+
+    Output {
+        status: std::process::ExitStatus::from_raw(code << 8),
+        stdout: Vec::new(),
+        stderr: stderr.to_vec(),
+    }
+}
+
+/// Runner recording the command lines, each answered by `answer` from its index and line
+#[cfg(test)]
+struct Stub<F> {
+    commands: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    answer: F,
+}
+
+#[cfg(test)]
+impl<F: Fn(usize, &str) -> Output + Sync> Stub<F> {
+    fn new(answer: F) -> Self {
+        Self {
+            commands: Default::default(),
+            answer,
+        }
+    }
+
+    /// Command lines run so far, clearing them resets the indexes given to `answer`
+    fn commands(&self) -> std::sync::Arc<std::sync::Mutex<Vec<String>>> {
+        self.commands.clone()
+    }
+}
+
+#[cfg(test)]
+impl<F: Fn(usize, &str) -> Output + Sync> Runner for Stub<F> {
+    fn output(&self, cmd: &mut Command) -> std::io::Result<Output> {
+        let cmd = display(cmd);
+        let mut commands = self.commands.lock().unwrap();
+        let output = (self.answer)(commands.len(), &cmd);
+        commands.push(cmd);
+        Ok(output)
+    }
+}
+
+#[test]
+fn test_dry_run() {
+    let mut registry = Registry::new("zou", "unreachable.invalid", 22, "/srv/sites");
+    registry.dry_run = true;
+
+    let rsync = registry
+        .rsync_command(Path::new("/tmp/site"), "my-site", false)
+        .unwrap();
+    assert_eq!(
+        "rsync -zr --exclude .zou --exclude .zouignore /tmp/site/ zou@unreachable.invalid:/srv/sites/my-site --dry-run --itemize-changes",
+        display(&rsync)
+    );
+
+    // ssh is never spawned, the host cannot be resolved
+    assert!(registry.delete("my-site").is_ok());
+    assert!(registry.list(false).is_ok());
+    assert!(registry.list_sites().unwrap().is_empty());
+}
+
+#[test]
+fn test_ssh_port() {
+    let registry = Registry::new("zou", "example.com", 2222, "/srv/sites");
+
+    let rsync = registry
+        .rsync_command(Path::new("/tmp/site"), "my-site", false)
+        .unwrap();
+    let args: Vec<_> = rsync.get_args().collect();
+    assert_eq!(["-e", "ssh -p 2222"], args[5..7]);
+
+    let ssh = registry.ssh_command("ls /srv/sites");
+    assert_eq!("ssh -p 2222 zou@example.com ls /srv/sites", display(&ssh));
+}
+
+#[test]
+fn test_identity() {
+    let mut registry = Registry::new("zou", "example.com", 22, "/srv/sites");
+    registry.identity = Some(PathBuf::from("/home/ci/deploy key"));
+
+    let rsync = registry
+        .rsync_command(Path::new("/tmp/site"), "my-site", false)
+        .unwrap();
+    let args: Vec<_> = rsync.get_args().collect();
+    assert_eq!(["-e", "ssh -i '/home/ci/deploy key'"], args[5..7]);
+
+    let ssh = registry.ssh_command("ls /srv/sites");
+    let args: Vec<_> = ssh.get_args().collect();
+    assert_eq!(["-i", "/home/ci/deploy key", "zou@example.com"], args[..3]);
+}
+
+#[test]
+fn test_zouignore() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join(IGNORE_FILE),
+        "# dependencies\nnode_modules/\n\n*.log\n!keep.log\n",
+    )
+    .unwrap();
+
+    let mut registry = Registry::new("zou", "example.com", 22, "/srv/sites");
+    registry.excludes = vec![".git".to_string()];
+
+    let rsync = registry
+        .rsync_command(dir.path(), "my-site", false)
+        .unwrap();
+    let args: Vec<_> = rsync.get_args().map(|arg| arg.to_str().unwrap()).collect();
+    assert_eq!(
+        [
+            "--exclude=.git",
+            "--include=keep.log",
+            "--exclude=*.log",
+            "--exclude=node_modules/"
+        ],
+        args[5..9]
+    );
+}
+
+#[test]
+fn test_force_deletes_extraneous_files() {
+    let registry = Registry::new("zou", "example.com", 22, "/srv/sites");
+    let source = Path::new("/tmp/site");
+
+    let rsync = registry.rsync_command(source, "my-site", false).unwrap();
+    assert!(!rsync.get_args().any(|arg| arg == "--delete"));
+
+    let rsync = registry.rsync_command(source, "my-site", true).unwrap();
+    assert!(rsync.get_args().any(|arg| arg == "--delete"));
+}
+
+#[test]
+fn test_parse_listing() {
+    let sites = parse_listing("my-site\t14096\t1681234567\nbroken line\n");
+    assert_eq!(
+        vec![Site {
+            name: "my-site".to_string(),
+            size: 14096,
+            modified: 1681234567,
+        }],
+        sites
+    );
+}
+
+#[test]
+fn test_listing_script() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("my sites");
+    std::fs::create_dir_all(root.join("blog")).unwrap();
+    std::fs::write(root.join("blog").join("index.html"), [b'a'; 10_000]).unwrap();
+    let run = |root: &Path| {
+        let output = Command::new("sh")
+            .args(["-c", &listing_script(root.to_str().unwrap())])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        parse_listing(&String::from_utf8(output.stdout).unwrap())
+    };
+
+    // the size of the files of the site, not of its directory
+    let sites = run(&root);
+    assert_eq!(1, sites.len());
+    assert_eq!("blog", sites[0].name);
+    assert!(sites[0].size >= 10_000, "{:?}", sites[0]);
+
+    std::fs::remove_dir_all(root.join("blog")).unwrap();
+    assert!(run(&root).is_empty());
+}
+
+#[test]
+fn test_quote() {
+    assert_eq!("/srv/sites/my-site", quote("/srv/sites/my-site"));
+    assert_eq!("'/srv/my sites'", quote("/srv/my sites"));
+    assert_eq!("'/srv/$HOME'", quote("/srv/$HOME"));
+    assert_eq!("'/srv/it'\\''s'", quote("/srv/it's"));
+    assert_eq!("''", quote(""));
+}
+
+#[test]
+fn test_rename_script() {
+    let script = rename_script("/srv/sites/old", "/srv/sites/new");
+    let status = |old_exists: bool, new_exists: bool| {
+        let dir = tempfile::tempdir().unwrap();
+        let (old, new) = (dir.path().join("old"), dir.path().join("new"));
+        if old_exists {
+            std::fs::create_dir(&old).unwrap();
+        }
+        if new_exists {
+            std::fs::create_dir(&new).unwrap();
+        }
+        let script = script
+            .replace("/srv/sites/old", old.to_str().unwrap())
+            .replace("/srv/sites/new", new.to_str().unwrap());
+        let success = Command::new("sh")
+            .args(["-c", &script])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap()
+            .success();
+        (success, old.exists(), new.exists())
+    };
+
+    assert_eq!((true, false, true), status(true, false));
+    assert_eq!((false, true, true), status(true, true));
+    assert_eq!((false, false, false), status(false, false));
+}
+
+#[test]
+fn test_retries() {
+    // fails with the ssh connection error twice, then succeeds
+    let flaky = Stub::new(|call, _| exited(if call < 2 { 255 } else { 0 }, b""));
+    let commands = flaky.commands();
+    let mut registry = Registry::new("zou", "example.com", 22, "/srv/sites");
+    registry.retry_delay = Duration::ZERO;
+    registry.runner = Box::new(flaky);
+
+    assert!(registry.delete("my-site").is_err());
+    assert_eq!(1, commands.lock().unwrap().len());
+
+    commands.lock().unwrap().clear();
+    registry.retries = 2;
+    assert!(registry.delete("my-site").is_ok());
+    assert_eq!(3, commands.lock().unwrap().len());
+
+    commands.lock().unwrap().clear();
+    assert!(registry.list(false).is_ok());
+    assert_eq!(3, commands.lock().unwrap().len());
+}
+
+#[test]
+fn test_validate_name() {
+    for name in [
+        "my-site",
+        "a",
+        "site42",
+        &"a".repeat(63),
+        &gen_name(NameStyle::Numbered, &[]),
+    ] {
+        assert!(validate_name(name).is_ok(), "{name}");
+    }
+    for name in [
+        "",
+        "My-Site",
+        "my.site",
+        "my site",
+        "-site",
+        "site-",
+        "../etc",
+        &"a".repeat(64),
+    ] {
+        assert!(validate_name(name).is_err(), "{name}");
+    }
+}
+
+#[test]
+fn test_publish_refuses_existing_name() {
+    // succeeds at everything, the remote site always existing
+    let existing = Stub::new(|_, _| exited(0, b""));
+    let commands = existing.commands();
+    let dir = tempfile::tempdir().unwrap();
+    let mut registry = Registry::new("zou", "example.com", 22, "/srv/sites");
+    registry.preflight = false;
+    registry.runner = Box::new(existing);
+
+    let error = registry
+        .publish(Some("my-site"), Some(dir.path().into()), false)
+        .unwrap_err();
+    assert!(error.to_string().contains("--force"));
+    assert_eq!(
+        ["ssh zou@example.com test -d /srv/sites/my-site"],
+        commands.lock().unwrap()[..]
+    );
+
+    commands.lock().unwrap().clear();
+    assert!(registry
+        .publish(Some("my-site"), Some(dir.path().into()), true)
+        .is_ok());
+    {
+        let commands = commands.lock().unwrap();
+        assert_eq!(1, commands.len());
+        assert!(commands[0].starts_with("rsync "));
+    }
+
+    registry.check_exists = false;
+    assert!(registry
+        .publish(Some("my-site"), Some(dir.path().into()), false)
+        .is_ok());
+}
+
+#[test]
+fn test_publish_saved_name_twice() {
+    let dir = tempfile::tempdir().unwrap();
+    let local_config = dir.path().join(crate::dotzou::FILE_NAME);
+    // the site does not exist when first checked
+    let stub = Stub::new(|call, _| exited(if call == 0 { 1 } else { 0 }, b""));
+    let commands = stub.commands();
+    let mut registry = Registry::new("zou", "example.com", 22, "/srv/sites");
+    registry.preflight = false;
+    registry.runner = Box::new(stub);
+
+    for _ in 0..2 {
+        registry.saved_name = crate::dotzou::get_var(&local_config, "ZOU_NAME").unwrap();
+        let name = registry
+            .saved_name
+            .clone()
+            .unwrap_or_else(|| "my-site".to_string());
+        let published = registry.publish(Some(&name), Some(dir.path().into()), false);
+        crate::dotzou::set_var(&local_config, "ZOU_NAME", &published.unwrap()).unwrap();
+    }
+    let commands = commands.lock().unwrap();
+    assert_eq!(3, commands.len());
+    assert!(commands[0].starts_with("ssh zou@example.com test -d"));
+    assert!(commands[1..].iter().all(|cmd| cmd.starts_with("rsync ")));
+}
+
+#[test]
+fn test_name_styles() {
+    // some of the adjectives are hyphenated, like bite-sized
+    let plain = gen_name(NameStyle::Plain, &[]);
+    let last = plain.rsplit('-').next().unwrap();
+    assert!(last.parse::<u32>().is_err(), "{plain}");
+    assert!(validate_name(&plain).is_ok());
+
+    let uuid = gen_name(NameStyle::Uuid, &[]);
+    assert_eq!(36, uuid.len());
+    assert_eq!(Some('4'), uuid.chars().nth(14));
+    assert!(validate_name(&uuid).is_ok());
+
+    let wordlist = vec!["rocket".to_string(), "comet".to_string()];
+    let name = gen_name(NameStyle::Numbered, &wordlist);
+    let parts: Vec<_> = name.split('-').collect();
+    assert_eq!(3, parts.len(), "{name}");
+    assert!(wordlist.iter().any(|word| word == parts[0]));
+    assert!(wordlist.iter().any(|word| word == parts[1]));
+    assert!(parts[2].parse::<u32>().is_ok());
+}
+
+#[test]
+fn test_failure_shows_stderr() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut registry = Registry::new("zou", "example.com", 22, "/srv/sites");
+    registry.preflight = false;
+    // fails like ssh refused by the registry
+    registry.runner = Box::new(Stub::new(|_, _| {
+        exited(255, b"zou@example.com: Permission denied (publickey).\n")
+    }));
+
+    let error = registry
+        .publish(Some("my-site"), Some(dir.path().into()), true)
+        .unwrap_err();
+    assert_eq!(
+        "unable to sync: zou@example.com: Permission denied (publickey).",
+        error.to_string()
+    );
+
+    let error = registry.delete("my-site").unwrap_err();
+    assert_eq!(
+        "unable to delete: zou@example.com: Permission denied (publickey).",
+        error.to_string()
+    );
+
+    assert_eq!(
+        "unable to sync (exit status: 23)",
+        failure("unable to sync", &exited(23, b"")).to_string()
+    );
+}
+
+#[test]
+fn test_publish_all() {
+    // refuses to sync the site "broken"
+    let partial = Stub::new(|_, cmd| {
+        if cmd.contains("/srv/sites/broken") {
+            exited(23, b"permission denied")
+        } else {
+            exited(0, b"")
+        }
+    });
+    let commands = partial.commands();
+    let dir = tempfile::tempdir().unwrap();
+    let mut registry = Registry::new("zou", "example.com", 22, "/srv/sites");
+    registry.check_exists = false;
+    registry.preflight = false;
+    registry.runner = Box::new(partial);
+
+    let sites: Vec<_> = ["docs", "broken", "blog", "shop"]
+        .into_iter()
+        .map(|name| manifest::Site {
+            dir: dir.path().into(),
+            name: name.to_string(),
+        })
+        .collect();
+    let results = registry.publish_all(&sites, false, 2);
+    assert_eq!(4, commands.lock().unwrap().len());
+    assert_eq!("docs", results[0].as_ref().unwrap());
+    assert_eq!(
+        "unable to sync: permission denied",
+        results[1].as_ref().unwrap_err().to_string()
+    );
+    assert_eq!("blog", results[2].as_ref().unwrap());
+    assert_eq!("shop", results[3].as_ref().unwrap());
+}
+
+#[test]
+fn test_progress() {
+    let mut registry = Registry::new("zou", "example.com", 22, "/srv/sites");
+    let args = |registry: &Registry| {
+        let rsync = registry
+            .rsync_command(Path::new("/tmp/site"), "my-site", false)
+            .unwrap();
+        rsync
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+    };
+    assert!(!args(&registry).iter().any(|arg| arg.contains("progress")));
+
+    registry.debug = true;
+    assert!(args(&registry).contains(&"--progress".to_string()));
+
+    registry.progress = true;
+    let with_progress = args(&registry);
+    assert!(with_progress.contains(&"--info=progress2".to_string()));
+    assert!(!with_progress.contains(&"--progress".to_string()));
+}
+
+#[test]
+fn test_preflight_script() {
+    let script = preflight_script("/srv/sites");
+    assert_eq!(
+        "test -d /srv/sites || { printf '%s does not exist\\n' /srv/sites >&2; exit 1; }; test -w /srv/sites || { printf '%s is not writable\\n' /srv/sites >&2; exit 1; }",
+        script
+    );
+    let registry = Registry::new("zou", "example.com", 22, "/srv/sites");
+    assert_eq!(
+        format!("ssh zou@example.com {script}"),
+        display(&registry.ssh_command(&script))
+    );
+
+    let dir = tempfile::tempdir().unwrap();
+    let run = |root_dir: &Path| {
+        let output = Command::new("sh")
+            .args(["-c", &preflight_script(root_dir.to_str().unwrap())])
+            .output()
+            .unwrap();
+        (
+            output.status.success(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+    assert_eq!((true, String::new()), run(dir.path()));
+    let missing = dir.path().join("missing");
+    assert_eq!(
+        (false, format!("{} does not exist\n", missing.display())),
+        run(&missing)
+    );
+}
+
+#[test]
+fn test_url() {
+    let mut registry = Registry::new("zou", "example.com", 22, "/srv/sites");
+    assert_eq!("http://my-site.example.com", registry.url("my-site"));
+    registry.https = true;
+    assert_eq!("https://my-site.example.com", registry.url("my-site"));
+    registry.public_base = Some("https://{name}.sites.example.org/".to_string());
+    assert_eq!(
+        "https://my-site.sites.example.org/",
+        registry.url("my-site")
+    );
+}
+
+#[test]
+fn test_logs_command() {
+    assert_eq!(
+        "journalctl -u zoubida -n 100 -f",
+        logs_command("zoubida", 100)
+    );
+    assert_eq!(
+        "tail -n 20 -F /var/log/zoubida.log",
+        logs_command("/var/log/zoubida.log", 20)
+    );
+    assert_eq!(
+        "tail -n 20 -F '/var/log/my logs/zoubida.log'",
+        logs_command("/var/log/my logs/zoubida.log", 20)
+    );
+
+    let mut registry = Registry::new("zou", "unreachable.invalid", 2222, "/srv/sites");
+    registry.dry_run = true;
+    assert!(registry.logs("zoubida", 100).is_ok());
+}
+
+#[test]
+fn test_parse_stats() {
+    let output = "
+Number of files: 132 (reg: 124, dir: 8)
+Number of created files: 124 (reg: 124)
+Number of deleted files: 0
+Number of regular files transferred: 124
+Total file size: 3,254,112 bytes
+Total transferred file size: 3,204,112 bytes
+Literal data: 3,204,112 bytes
+Matched data: 0 bytes
+File list size: 0
+File list generation time: 0.001 seconds
+File list transfer time: 0.000 seconds
+Total bytes sent: 1,245,876
+Total bytes received: 2,417
+
+sent 1,245,876 bytes  received 2,417 bytes  832,195.33 bytes/sec
+total size is 3,254,112  speedup is 2.61
+";
+    assert_eq!(Some((124, 3_204_112)), parse_stats(output));
+    assert_eq!(None, parse_stats("sending incremental file list\n"));
+
+    assert_eq!("512 B", human_size(512));
+    assert_eq!("3.2 MB", human_size(3_204_112));
+    assert_eq!("1.0 MB", human_size(999_999));
+    assert_eq!("1.5 GB", human_size(1_500_000_000));
+}
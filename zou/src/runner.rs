@@ -0,0 +1,145 @@
+use std::io::{Read, Write};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::time::Duration;
+
+/// Spawns the commands of a [`Registry`](crate::registry::Registry), replaceable in tests
+///
+/// It is shared by the workers publishing several sites at once.
+pub trait Runner: Sync {
+    fn output(&self, cmd: &mut Command) -> std::io::Result<Output>;
+
+    /// Like [`Runner::output`], showing the stdout of `cmd` as it comes up to the line starting
+    /// with `until`
+    fn tee(&self, cmd: &mut Command, _until: &str) -> std::io::Result<Output> {
+        self.output(cmd)
+    }
+}
+
+/// Runs the commands for real
+pub struct System;
+
+impl Runner for System {
+    fn output(&self, cmd: &mut Command) -> std::io::Result<Output> {
+        cmd.output()
+    }
+
+    fn tee(&self, cmd: &mut Command, until: &str) -> std::io::Result<Output> {
+        let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+        let mut stderr = child.stderr.take().expect("piped stderr");
+        let errors = std::thread::spawn(move || {
+            let mut errors = Vec::new();
+            stderr.read_to_end(&mut errors).map(|_| errors)
+        });
+
+        let mut stdout = child.stdout.take().expect("piped stdout");
+        let mut console = std::io::stdout();
+        let marker = format!("\n{until}");
+        let (mut captured, mut shown, mut hidden) = (Vec::new(), 0, false);
+        let mut chunk = [0; 8192];
+        loop {
+            let read = stdout.read(&mut chunk)?;
+            captured.extend_from_slice(&chunk[..read]);
+            if !hidden {
+                // the start of the marker may be in the next chunk
+                let end = match find(&captured[shown..], marker.as_bytes()) {
+                    Some(found) => {
+                        hidden = true;
+                        shown + found + 1
+                    }
+                    None if read == 0 => captured.len(),
+                    None => captured.len().saturating_sub(marker.len()).max(shown),
+                };
+                console.write_all(&captured[shown..end])?;
+                console.flush()?;
+                shown = end;
+            }
+            if read == 0 {
+                break;
+            }
+        }
+
+        let status = child.wait()?;
+        let stderr = errors.join().expect("stderr reader panicked")?;
+        Ok(Output {
+            status,
+            stdout: captured,
+            stderr,
+        })
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Error of spawning `cmd`, telling to install its program when it is missing
+pub fn spawn_error(cmd: &Command, error: std::io::Error) -> anyhow::Error {
+    let program = cmd.get_program().to_string_lossy();
+    if error.kind() == std::io::ErrorKind::NotFound {
+        anyhow::anyhow!("{program} not found in PATH — please install {program}")
+    } else {
+        anyhow::Error::new(error).context(format!("unable to run {program}"))
+    }
+}
+
+/// Whether `status` reports a network failure rather than a refusal of the command
+///
+/// ssh exits with 255 when the connection fails, rsync with 10 (socket I/O), 12 (protocol data
+/// stream), 30 (timeout) or 35 (daemon connection timeout).
+pub fn is_transient(status: &ExitStatus) -> bool {
+    matches!(status.code(), Some(10 | 12 | 30 | 35 | 255))
+}
+
+/// Calls `attempt` up to `retries` more times while it reports a transient failure, doubling
+/// `delay` after each one
+pub fn retry<T>(
+    retries: u32,
+    mut delay: Duration,
+    mut attempt: impl FnMut() -> std::io::Result<T>,
+    transient: impl Fn(&T) -> bool,
+) -> std::io::Result<T> {
+    for _ in 0..retries {
+        let result = attempt()?;
+        if !transient(&result) {
+            return Ok(result);
+        }
+        eprintln!("⟳ transient failure, retrying in {}s", delay.as_secs_f32());
+        std::thread::sleep(delay);
+        delay *= 2;
+    }
+    attempt()
+}
+
+#[test]
+fn test_tee() {
+    let mut cmd = Command::new("sh");
+    cmd.args([
+        "-c",
+        "echo sending; echo; echo 'Number of files: 2'; echo oops >&2; exit 3",
+    ]);
+    let output = System.tee(&mut cmd, "Number of files:").unwrap();
+    assert_eq!(Some(3), output.status.code());
+    assert_eq!(
+        "sending\n\nNumber of files: 2\n",
+        String::from_utf8(output.stdout).unwrap()
+    );
+    assert_eq!("oops\n", String::from_utf8(output.stderr).unwrap());
+}
+
+#[test]
+fn test_spawn_error() {
+    let mut cmd = Command::new("zou-missing-binary");
+    let error = System.output(&mut cmd).unwrap_err();
+    assert_eq!(
+        "zou-missing-binary not found in PATH — please install zou-missing-binary",
+        spawn_error(&cmd, error).to_string()
+    );
+
+    let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+    assert_eq!(
+        "unable to run rsync",
+        spawn_error(&Command::new("rsync"), denied).to_string()
+    );
+}
@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+/// Status code of a `HEAD` request to `url`, redirects followed
+pub fn head(url: &str) -> Result<u16, ureq::Error> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .timeout_global(Some(Duration::from_secs(10)))
+        .build()
+        .into();
+    let response = agent.head(url).call()?;
+    Ok(response.status().as_u16())
+}
+
+/// Prints whether the site `name` of `host` answers, over HTTPS or else HTTP
+///
+/// Returns whether it does with a success or redirect status.
+pub fn report(name: &str, host: &str) -> bool {
+    let https = format!("https://{name}.{host}");
+    let failure = match head(&https) {
+        Ok(code) => return print_status(&https, code, "TLS valid"),
+        Err(err) => err,
+    };
+    println!("✘ {https}: {failure}");
+
+    let http = format!("http://{name}.{host}");
+    match head(&http) {
+        Ok(code) => print_status(&http, code, "no TLS"),
+        Err(err) => {
+            println!("✘ {http}: {err}");
+            false
+        }
+    }
+}
+
+/// Prints whether `url` answers, returning whether it does with a success or redirect status
+pub fn report_url(url: &str) -> bool {
+    let tls = if url.starts_with("https://") {
+        "TLS valid"
+    } else {
+        "no TLS"
+    };
+    match head(url) {
+        Ok(code) => print_status(url, code, tls),
+        Err(err) => {
+            println!("✘ {url}: {err}");
+            false
+        }
+    }
+}
+
+fn print_status(url: &str, code: u16, tls: &str) -> bool {
+    let live = code < 400;
+    let mark = if live { '✔' } else { '✘' };
+    println!("{mark} {url}: {code} ({tls})");
+    live
+}
+
+#[test]
+fn test_head() {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0; 1024];
+        let _ = stream.read(&mut request).unwrap();
+        stream
+            .write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n")
+            .unwrap();
+    });
+
+    assert_eq!(404, head(&url).unwrap());
+    assert!(head("http://127.0.0.1:1/").is_err());
+}
@@ -0,0 +1,233 @@
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use flate2::{write::GzEncoder, Compression};
+
+/// zoubida upload endpoint publishing sites over HTTP, for registries without SSH access
+pub struct HttpUpload {
+    /// URL of the endpoint, like `https://example.com/__upload`
+    pub url: String,
+    pub token: String,
+}
+
+impl HttpUpload {
+    /// Replaces the content of the site `name` with `archive`
+    ///
+    /// Unless `replace` is set, the upload is refused when the site already exists.
+    pub fn put(&self, name: &str, archive: &[u8], replace: bool) -> anyhow::Result<()> {
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .http_status_as_error(false)
+            .build()
+            .into();
+        let url = format!("{}/{name}", self.url.trim_end_matches('/'));
+        let mut request = agent
+            .put(&url)
+            .header("Authorization", &format!("Bearer {}", self.token))
+            .header("Content-Type", "application/gzip");
+        if !replace {
+            request = request.header("If-None-Match", "*");
+        }
+        let mut response = request
+            .send(archive)
+            .with_context(|| format!("unable to upload to {url}"))?;
+
+        if response.status() == 412 {
+            bail!("\"{name}\" already exists, use --force to replace its content");
+        }
+        if !response.status().is_success() {
+            let message = response.body_mut().read_to_string().unwrap_or_default();
+            bail!("upload refused ({}): {}", response.status(), message.trim());
+        }
+        Ok(())
+    }
+}
+
+/// Gzipped tarball of `source`, without the `.zou` files nor those matching `patterns`
+pub fn tarball(source: &Path, patterns: &[&str]) -> anyhow::Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    append_dir(&mut builder, source, "", patterns)?;
+    let archive = builder.into_inner()?.finish()?;
+    Ok(archive)
+}
+
+fn append_dir(
+    builder: &mut tar::Builder<GzEncoder<Vec<u8>>>,
+    dir: &Path,
+    prefix: &str,
+    patterns: &[&str],
+) -> anyhow::Result<()> {
+    let mut entries = std::fs::read_dir(dir)
+        .with_context(|| format!("unable to read {dir:?}"))?
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if prefix.is_empty()
+            && (name == crate::dotzou::FILE_NAME || name == crate::registry::IGNORE_FILE)
+        {
+            continue;
+        }
+        let path = format!("{prefix}{name}");
+        // like rsync without --links, symlinks are left out
+        let file_type = entry.file_type()?;
+        if is_ignored(patterns, &path, file_type.is_dir()) {
+            continue;
+        }
+        if file_type.is_dir() {
+            builder.append_dir(&path, entry.path())?;
+            append_dir(builder, &entry.path(), &format!("{path}/"), patterns)?;
+        } else if file_type.is_file() {
+            builder.append_path_with_name(entry.path(), &path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether the last of the gitignore-style `patterns` matching `path` excludes it
+fn is_ignored(patterns: &[&str], path: &str, is_dir: bool) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    patterns
+        .iter()
+        .rev()
+        .find_map(|pattern| {
+            let (pattern, negated) = match pattern.strip_prefix('!') {
+                Some(pattern) => (pattern, true),
+                None => (*pattern, false),
+            };
+            let (pattern, dir_only) = match pattern.strip_suffix('/') {
+                Some(pattern) => (pattern, true),
+                None => (pattern, false),
+            };
+            if dir_only && !is_dir {
+                return None;
+            }
+            // patterns with a slash are relative to the root, the others match names anywhere
+            let matched = match pattern.strip_prefix('/') {
+                Some(pattern) => glob(pattern.as_bytes(), path.as_bytes()),
+                None if pattern.contains('/') => glob(pattern.as_bytes(), path.as_bytes()),
+                None => glob(pattern.as_bytes(), name.as_bytes()),
+            };
+            matched.then_some(!negated)
+        })
+        .unwrap_or(false)
+}
+
+/// Matches `text` against `pattern`, `*` and `?` not matching `/` unlike `**`
+fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', rest @ ..] => {
+            let rest = rest.strip_prefix(b"/").unwrap_or(rest);
+            (0..=text.len()).any(|i| glob(rest, &text[i..]))
+        }
+        [b'*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != b'/')
+            .any(|i| glob(rest, &text[i..])),
+        [b'?', rest @ ..] => matches!(text, [c, tail @ ..] if *c != b'/' && glob(rest, tail)),
+        [c, rest @ ..] => matches!(text, [t, tail @ ..] if t == c && glob(rest, tail)),
+    }
+}
+
+/// Answers one request with `status`, returning the request as received
+#[cfg(test)]
+fn serve_once(status: &str) -> (String, std::thread::JoinHandle<String>) {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/__upload", listener.local_addr().unwrap());
+    let status = status.to_string();
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let (mut head, mut length) = (String::new(), 0);
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+            if line == "\r\n" {
+                break;
+            }
+            head.push_str(&line);
+        }
+        reader.read_exact(&mut vec![0; length]).unwrap();
+        let response =
+            format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+        reader.get_mut().write_all(response.as_bytes()).unwrap();
+        head
+    });
+    (url, server)
+}
+
+#[test]
+fn test_put() {
+    let (url, server) = serve_once("412 Precondition Failed");
+    let upload = HttpUpload {
+        url,
+        token: "s3cr3t".to_string(),
+    };
+    let error = upload.put("blog", b"archive", false).unwrap_err();
+    assert_eq!(
+        "\"blog\" already exists, use --force to replace its content",
+        error.to_string()
+    );
+    let head = server.join().unwrap().to_ascii_lowercase();
+    assert!(head.starts_with("put /__upload/blog "), "{head}");
+    assert!(head.contains("if-none-match: *\r\n"), "{head}");
+
+    let (url, server) = serve_once("201 Created");
+    let upload = HttpUpload { url, ..upload };
+    upload.put("blog", b"archive", true).unwrap();
+    let head = server.join().unwrap().to_ascii_lowercase();
+    assert!(!head.contains("if-none-match"), "{head}");
+}
+
+#[test]
+fn test_is_ignored() {
+    let patterns = [
+        "node_modules/",
+        "*.log",
+        "!keep.log",
+        "/build",
+        "docs/**/*.tmp",
+    ];
+    assert!(is_ignored(&patterns, "node_modules", true));
+    assert!(is_ignored(&patterns, "app/node_modules", true));
+    assert!(!is_ignored(&patterns, "node_modules", false));
+    assert!(is_ignored(&patterns, "logs/debug.log", false));
+    assert!(!is_ignored(&patterns, "logs/keep.log", false));
+    assert!(is_ignored(&patterns, "build", true));
+    assert!(!is_ignored(&patterns, "src/build", true));
+    assert!(is_ignored(&patterns, "docs/a/b/draft.tmp", false));
+    assert!(is_ignored(&patterns, "docs/draft.tmp", false));
+    assert!(!is_ignored(&patterns, "index.html", false));
+}
+
+#[test]
+fn test_tarball() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("node_modules/lib")).unwrap();
+    std::fs::write(dir.path().join("node_modules/lib/index.js"), "").unwrap();
+    std::fs::create_dir(dir.path().join("assets")).unwrap();
+    std::fs::write(dir.path().join("assets/app.js"), "app").unwrap();
+    std::fs::write(dir.path().join("index.html"), "hi").unwrap();
+    std::fs::write(dir.path().join(".zou"), "ZOU_NAME=my-site").unwrap();
+
+    let archive = tarball(dir.path(), &["node_modules/"]).unwrap();
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(&archive[..]));
+    let paths: Vec<String> = archive
+        .entries()
+        .unwrap()
+        .map(|entry| {
+            entry
+                .unwrap()
+                .path()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    assert_eq!(["assets", "assets/app.js", "index.html"], paths[..]);
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::ServerConfig;
+use rustls_acme::acme::ACME_TLS_ALPN_NAME;
+use rustls_acme::caches::DirCache;
+use rustls_acme::{AcmeConfig, ResolvesServerCertAcme};
+
+use crate::tls::{self, TlsVersion};
+
+/// Certificates obtained from Let's Encrypt, one per domain so that domains can be added while
+/// serving and never reach the limit of names of a single certificate
+///
+/// Certificates are validated with TLS-ALPN-01, which means the HTTPS listener must be reachable on port 443.
+pub struct Certificates {
+    resolvers: Mutex<HashMap<String, Arc<ResolvesServerCertAcme>>>,
+    contacts: Vec<String>,
+    cache_dir: Option<PathBuf>,
+    staging: bool,
+}
+
+impl Certificates {
+    pub fn new(emails: &[String], cache_dir: Option<PathBuf>, staging: bool) -> Self {
+        Self {
+            resolvers: Mutex::default(),
+            contacts: emails
+                .iter()
+                .map(|email| format!("mailto:{email}"))
+                .collect(),
+            cache_dir,
+            staging,
+        }
+    }
+
+    /// Starts requesting, then renewing, the certificate of `domain` in the background, unless it already is
+    pub fn add(&self, domain: &str) {
+        let domain = domain.to_ascii_lowercase();
+        let mut resolvers = self.resolvers.lock().unwrap();
+        if resolvers.contains_key(&domain) {
+            return;
+        }
+
+        let mut state = AcmeConfig::new([&domain])
+            .contact(&self.contacts)
+            .cache_option(self.cache_dir.clone().map(DirCache::new))
+            .directory_lets_encrypt(!self.staging)
+            .state();
+        resolvers.insert(domain.clone(), state.resolver());
+        tracing::info!("requesting a certificate for {domain}");
+
+        tokio::spawn(async move {
+            while let Some(event) = state.next().await {
+                match event {
+                    Ok(event) => tracing::info!("acme {domain}: {event:?}"),
+                    Err(error) => tracing::error!("acme {domain}: {error:?}"),
+                }
+            }
+        });
+    }
+}
+
+impl ResolvesServerCert for Certificates {
+    /// The certificate of the requested name, or its TLS-ALPN-01 challenge one
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let domain = client_hello.server_name()?.to_ascii_lowercase();
+        let resolver = self.resolvers.lock().unwrap().get(&domain).cloned()?;
+        resolver.resolve(client_hello)
+    }
+}
+
+/// TLS config serving `certificates`, answering the TLS-ALPN-01 challenges too
+pub fn server_config(
+    certificates: Arc<Certificates>,
+    min_version: TlsVersion,
+) -> anyhow::Result<ServerConfig> {
+    let mut config = ServerConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(min_version.versions())?
+        .with_no_client_auth()
+        .with_cert_resolver(certificates);
+    // only ever picked by the validation server, which offers nothing else
+    config.alpn_protocols = tls::alpn_protocols();
+    config.alpn_protocols.push(ACME_TLS_ALPN_NAME.to_vec());
+    Ok(config)
+}
+
+/// Adds the domains of the sites created in `root_dir` since the last scan, every `interval`
+pub async fn watch_sites(
+    certificates: Arc<Certificates>,
+    domains: Vec<String>,
+    root_dir: PathBuf,
+    interval: Duration,
+) {
+    let mut ticks = tokio::time::interval(interval);
+    loop {
+        ticks.tick().await;
+        match expand_wildcards(&domains, &root_dir) {
+            Ok(expanded) => {
+                for domain in &expanded {
+                    certificates.add(domain);
+                }
+            }
+            Err(error) => tracing::warn!("unable to list sites for ACME domains: {error}"),
+        }
+    }
+}
+
+/// Expands `*.<base>` domains to one domain per site directory in `root_dir`
+///
+/// TLS-ALPN-01 cannot issue wildcard certificates, so in SUBDOMAIN mode the sites are
+/// enumerated instead, `@` being the apex domain.
+pub fn expand_wildcards(domains: &[String], root_dir: &Path) -> std::io::Result<Vec<String>> {
+    let mut expanded = Vec::new();
+    for domain in domains {
+        match domain.strip_prefix("*.") {
+            Some(base) => {
+                for entry in std::fs::read_dir(root_dir)? {
+                    let entry = entry?;
+                    if !entry.file_type()?.is_dir() {
+                        continue;
+                    }
+                    match entry.file_name().to_str() {
+                        Some("@") => expanded.push(base.to_string()),
+                        Some(site) => expanded.push(format!("{site}.{base}")),
+                        None => {}
+                    }
+                }
+            }
+            None => expanded.push(domain.clone()),
+        }
+    }
+    expanded.sort();
+    expanded.dedup();
+    Ok(expanded)
+}
+
+#[test]
+fn test_expand_wildcards() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("@")).unwrap();
+    std::fs::create_dir(dir.path().join("blog")).unwrap();
+    std::fs::write(dir.path().join("notes.txt"), "").unwrap();
+
+    let domains = ["*.braindead.fr".to_string(), "example.com".to_string()];
+    assert_eq!(
+        vec!["blog.braindead.fr", "braindead.fr", "example.com"],
+        expand_wildcards(&domains, dir.path()).unwrap()
+    );
+}
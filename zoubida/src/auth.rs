@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use axum::extract::State;
+use axum::http::{header, HeaderValue, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+
+use crate::{AppState, ServeMode};
+
+pub struct Credentials {
+    user: String,
+    pass: String,
+}
+
+impl Credentials {
+    pub fn new(user: impl Into<String>, pass: impl Into<String>) -> Self {
+        Self {
+            user: user.into(),
+            pass: pass.into(),
+        }
+    }
+
+    /// Checks an `Authorization` header value against these credentials
+    fn check(&self, authorization: &HeaderValue) -> bool {
+        let Some(encoded) = authorization
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Basic "))
+        else {
+            return false;
+        };
+        let Ok(decoded) = base64::engine::general_purpose::STANDARD.decode(encoded.trim()) else {
+            return false;
+        };
+        let Some(colon) = decoded.iter().position(|b| *b == b':') else {
+            return false;
+        };
+        let (user, pass) = (&decoded[..colon], &decoded[colon + 1..]);
+        // evaluate both so that a wrong user takes as long as a wrong password
+        let user_ok = constant_time_eq(user, self.user.as_bytes());
+        let pass_ok = constant_time_eq(pass, self.pass.as_bytes());
+        user_ok & pass_ok
+    }
+}
+
+/// Global credentials, optionally overridden per subdomain in SUBDOMAIN mode
+pub struct Auth {
+    global: Option<Credentials>,
+    subdomains: HashMap<String, Credentials>,
+}
+
+impl Auth {
+    pub fn new(global: Option<Credentials>) -> Self {
+        Self {
+            global,
+            subdomains: HashMap::new(),
+        }
+    }
+
+    /// Loads per-subdomain credentials from lines of `<subdomain>:<user>:<password>`
+    pub fn load_file(&mut self, path: &Path) -> anyhow::Result<()> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("unable to read auth file {path:?}"))?;
+
+        for (n, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(3, ':');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(subdomain), Some(user), Some(pass)) => {
+                    self.subdomains
+                        .insert(subdomain.to_ascii_lowercase(), Credentials::new(user, pass));
+                }
+                _ => bail!(
+                    "invalid auth file {path:?} line {}, expected \"<subdomain>:<user>:<password>\"",
+                    n + 1
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn credentials(&self, subdomain: Option<&str>) -> Option<&Credentials> {
+        subdomain
+            .and_then(|subdomain| self.subdomains.get(subdomain))
+            .or(self.global.as_ref())
+    }
+}
+
+/// Requires HTTP Basic credentials, answering `401` with a challenge on mismatch
+pub async fn basic_auth<B>(
+    State(state): State<Arc<AppState>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let host = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .map(String::from);
+
+    let (subdomain, site) = match (&state.mode, host) {
+        (ServeMode::Subdomain(_), Some(host)) => {
+            let site = match state.site_dir(&host) {
+                Ok(dir) => Some(state.site_config(&dir).await),
+                Err(_) => None,
+            };
+            (state.subdomain(&host), site)
+        }
+        _ => (None, None),
+    };
+
+    // a site's zoubida.toml takes precedence over the auth file and the global credentials
+    let credentials = site
+        .as_ref()
+        .and_then(|site| site.auth.as_ref())
+        .or_else(|| {
+            state
+                .auth
+                .as_ref()
+                .and_then(|auth| auth.credentials(subdomain.as_deref()))
+        });
+    let Some(credentials) = credentials else {
+        return next.run(request).await;
+    };
+
+    match request.headers().get(header::AUTHORIZATION) {
+        Some(authorization) if credentials.check(authorization) => next.run(request).await,
+        _ => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Basic realm=\"zoubida\"")],
+            "Unauthorized",
+        )
+            .into_response(),
+    }
+}
+
+/// Compares in time depending only on the length of the inputs
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[test]
+fn test_constant_time_eq() {
+    assert!(constant_time_eq(b"hunter2", b"hunter2"));
+    assert!(!constant_time_eq(b"hunter2", b"hunter3"));
+    assert!(!constant_time_eq(b"hunter2", b"hunter"));
+}
+
+#[test]
+fn test_check_credentials() {
+    let credentials = Credentials::new("admin", "pa:ss");
+    // admin:pa:ss
+    assert!(credentials.check(&HeaderValue::from_static("Basic YWRtaW46cGE6c3M=")));
+    // admin:pass
+    assert!(!credentials.check(&HeaderValue::from_static("Basic YWRtaW46cGFzcw==")));
+    assert!(!credentials.check(&HeaderValue::from_static("Bearer YWRtaW46cGE6c3M=")));
+}
@@ -0,0 +1,80 @@
+use std::fs::Metadata;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use axum::extract::State;
+use axum::http::{header, HeaderValue, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::AppState;
+
+/// Strong validator derived from the file size and modification time
+///
+/// Precompressed variants are different representations of the same file, so the
+/// `Content-Encoding` is part of the tag. The tag is `weak` when compression happens after it
+/// is computed, the encoded bytes then depending on the negotiation.
+pub fn etag(
+    metadata: &Metadata,
+    encoding: Option<&HeaderValue>,
+    weak: bool,
+) -> Option<HeaderValue> {
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    let prefix = if weak { "W/" } else { "" };
+    let mut tag = format!("{prefix}\"{:x}-{:x}", metadata.len(), modified.as_nanos());
+    if let Some(encoding) = encoding.and_then(|encoding| encoding.to_str().ok()) {
+        tag.push('-');
+        tag.push_str(encoding);
+    }
+    tag.push('"');
+    HeaderValue::from_str(&tag).ok()
+}
+
+/// Whether an `If-None-Match` header matches `etag`, using the weak comparison of RFC 9110
+pub fn if_none_match(header: &HeaderValue, etag: &HeaderValue) -> bool {
+    let Ok(header) = header.to_str() else {
+        return false;
+    };
+    let etag = etag.to_str().unwrap_or_default();
+
+    header.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.trim_start_matches("W/") == etag.trim_start_matches("W/")
+    })
+}
+
+/// Appends the configured `Cache-Control` to successful responses
+pub async fn cache_control<B>(
+    State(state): State<Arc<AppState>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let mut response = next.run(request).await;
+    if let Some(cache_control) = &state.cache_control {
+        let status = response.status();
+        if status.is_success() || status == StatusCode::NOT_MODIFIED {
+            response
+                .headers_mut()
+                .insert(header::CACHE_CONTROL, cache_control.clone());
+        }
+    }
+    response
+}
+
+#[test]
+fn test_if_none_match() {
+    let etag = HeaderValue::from_static("\"5-abc\"");
+    assert!(if_none_match(&HeaderValue::from_static("\"5-abc\""), &etag));
+    assert!(if_none_match(
+        &HeaderValue::from_static("W/\"5-abc\""),
+        &etag
+    ));
+    assert!(if_none_match(
+        &HeaderValue::from_static("\"1-a\", \"5-abc\""),
+        &etag
+    ));
+    assert!(if_none_match(&HeaderValue::from_static("*"), &etag));
+    assert!(!if_none_match(
+        &HeaderValue::from_static("\"5-abd\""),
+        &etag
+    ));
+}
@@ -0,0 +1,132 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::{header, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use clap::ValueEnum;
+
+use crate::{AppState, ServeMode};
+
+/// Which of `www.<domain>` and `<domain>` requests are redirected to
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum CanonicalHost {
+    StripWww,
+    ForceWww,
+}
+
+impl CanonicalHost {
+    /// `host` in its canonical form, port included, or `None` when it already is
+    ///
+    /// With a `base` domain only the apex and `www.<base>` are changed, otherwise any host but an IP
+    /// address or a single label like `localhost` is.
+    pub fn apply(self, host: &str, base: Option<&str>) -> Option<String> {
+        let name = crate::strip_port(host).to_ascii_lowercase();
+        let port = &host[crate::strip_port(host).len()..];
+        if !name.contains('.') || name.parse::<IpAddr>().is_ok() || name.starts_with('[') {
+            return None;
+        }
+        if let Some(base) = base {
+            let base = base.trim_start_matches('.');
+            if name != base && name.strip_prefix("www.") != Some(base) {
+                return None;
+            }
+        }
+
+        let canonical = match (self, name.strip_prefix("www.")) {
+            (CanonicalHost::StripWww, Some(apex)) => apex.to_string(),
+            (CanonicalHost::ForceWww, None) => format!("www.{name}"),
+            _ => return None,
+        };
+        Some(format!("{canonical}{port}"))
+    }
+}
+
+/// Permanently redirects requests to the canonical host, keeping their path and query
+///
+/// The redirect is scheme-relative so that it stays on HTTP or HTTPS.
+pub async fn redirect<B>(
+    State(state): State<Arc<AppState>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let Some(canonical_host) = state.canonical_host else {
+        return next.run(request).await;
+    };
+    let host = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .unwrap_or_default();
+    let base = match &state.mode {
+        ServeMode::Subdomain(_) => Some(match &state.base_domain {
+            Some(base) => base.clone(),
+            None => last_labels(crate::strip_port(host)).to_ascii_lowercase(),
+        }),
+        ServeMode::Path(_) | ServeMode::File(_) => None,
+    };
+
+    match canonical_host.apply(host, base.as_deref()) {
+        Some(canonical) => {
+            let path = request
+                .uri()
+                .path_and_query()
+                .map_or("/", |path| path.as_str());
+            (
+                StatusCode::MOVED_PERMANENTLY,
+                [(header::LOCATION, format!("//{canonical}{path}"))],
+            )
+                .into_response()
+        }
+        None => next.run(request).await,
+    }
+}
+
+/// The last two labels of `host`, being the apex domain when no `--base-domain` is set
+fn last_labels(host: &str) -> &str {
+    match host.rmatch_indices('.').nth(1) {
+        Some((index, _)) => &host[index + 1..],
+        None => host,
+    }
+}
+
+#[test]
+fn test_apply() {
+    let strip = |host| CanonicalHost::StripWww.apply(host, None);
+    assert_eq!(Some("example.com".to_string()), strip("www.example.com"));
+    assert_eq!(
+        Some("example.com:8080".to_string()),
+        strip("WWW.Example.com:8080")
+    );
+    assert_eq!(None, strip("example.com"));
+    assert_eq!(None, strip("localhost:8080"));
+    assert_eq!(None, strip("127.0.0.1"));
+
+    let force = |host| CanonicalHost::ForceWww.apply(host, None);
+    assert_eq!(Some("www.example.com".to_string()), force("example.com"));
+    assert_eq!(None, force("www.example.com"));
+    assert_eq!(None, force("[::1]:8080"));
+
+    // only the apex/www pair of the base domain
+    let base = Some("example.co.uk");
+    assert_eq!(
+        Some("example.co.uk".to_string()),
+        CanonicalHost::StripWww.apply("www.example.co.uk", base)
+    );
+    assert_eq!(
+        None,
+        CanonicalHost::StripWww.apply("www.blog.example.co.uk", base)
+    );
+    assert_eq!(
+        None,
+        CanonicalHost::ForceWww.apply("blog.example.co.uk", base)
+    );
+    assert_eq!(
+        Some("www.example.co.uk".to_string()),
+        CanonicalHost::ForceWww.apply("example.co.uk", base)
+    );
+
+    assert_eq!("example.com", last_labels("www.example.com"));
+    assert_eq!("example.com", last_labels("example.com"));
+}
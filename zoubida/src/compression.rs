@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use axum::http::{header, Extensions, HeaderMap, StatusCode, Version};
+use tower_http::compression::predicate::{Predicate, SizeAbove};
+use tower_http::compression::{CompressionLayer, CompressionLevel};
+
+/// Content types that are already compressed and would not benefit from another pass
+const COMPRESSED_TYPES: &[&str] = &[
+    "application/gzip",
+    "application/x-gzip",
+    "application/zip",
+    "application/x-bzip2",
+    "application/x-xz",
+    "application/x-7z-compressed",
+    "application/zstd",
+    "audio/",
+    "video/",
+    "font/woff",
+];
+
+pub struct Options {
+    /// responses of fewer bytes are sent as they are
+    pub min_size: u16,
+    /// brotli quality, clamped to 9 for gzip
+    pub level: u32,
+    /// content types to compress, `type/*` standing for every subtype
+    pub types: Vec<String>,
+}
+
+/// Compresses the responses `options` allow, negotiated through `Accept-Encoding`
+pub fn layer(options: &Options) -> CompressionLayer<impl Predicate> {
+    let types: Arc<[String]> = options
+        .types
+        .iter()
+        .map(|t| t.to_ascii_lowercase())
+        .collect();
+    let allowed = move |_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions| {
+        let content_type = content_type(headers);
+        types.iter().any(|allowed| match allowed.strip_suffix('*') {
+            Some(prefix) => content_type.starts_with(prefix),
+            None => content_type == *allowed,
+        })
+    };
+    CompressionLayer::new()
+        .quality(CompressionLevel::Precise(options.level))
+        .compress_when(
+            SizeAbove::new(options.min_size)
+                .and(allowed)
+                .and(not_compressed),
+        )
+}
+
+/// Lowercase media type of the response, without its parameters
+fn content_type(headers: &HeaderMap) -> String {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let essence = content_type.split(';').next().unwrap_or_default();
+    essence.trim().to_ascii_lowercase()
+}
+
+fn not_compressed(_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions) -> bool {
+    let content_type = content_type(headers);
+
+    !COMPRESSED_TYPES
+        .iter()
+        .any(|compressed| content_type.starts_with(compressed))
+}
@@ -0,0 +1,51 @@
+use anyhow::Context;
+use axum::http::{HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowHeaders, AllowOrigin, CorsLayer};
+
+/// Builds the CORS layer, `None` when no origin is allowed
+///
+/// A `*` origin (or header) allows any.
+pub fn layer(
+    origins: &[String],
+    methods: &[String],
+    headers: &[String],
+) -> anyhow::Result<Option<CorsLayer>> {
+    if origins.is_empty() {
+        return Ok(None);
+    }
+
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins = origins
+            .iter()
+            .map(|origin| HeaderValue::from_str(origin))
+            .collect::<Result<Vec<_>, _>>()
+            .context("invalid --cors origin")?;
+        AllowOrigin::list(origins)
+    };
+
+    let methods = methods
+        .iter()
+        .map(|method| Method::from_bytes(method.to_uppercase().as_bytes()))
+        .collect::<Result<Vec<_>, _>>()
+        .context("invalid --cors-methods value")?;
+
+    let allow_headers = if headers.iter().any(|header| header == "*") {
+        AllowHeaders::any()
+    } else {
+        let headers = headers
+            .iter()
+            .map(|header| HeaderName::from_bytes(header.as_bytes()))
+            .collect::<Result<Vec<_>, _>>()
+            .context("invalid --cors-headers value")?;
+        AllowHeaders::list(headers)
+    };
+
+    Ok(Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods(methods)
+            .allow_headers(allow_headers),
+    ))
+}
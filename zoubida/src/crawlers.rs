@@ -0,0 +1,146 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use axum::http::{header, HeaderValue};
+use axum::response::{IntoResponse, Response};
+use percent_encoding::utf8_percent_encode;
+
+use crate::listing::{escape, SEGMENT};
+
+/// Most URLs a sitemap may list
+const SITEMAP_MAX_URLS: usize = 50_000;
+
+/// `robots.txt` allowing every crawler, pointing them at the sitemap of `base` when there is one
+pub fn robots(sitemap_base: Option<&str>) -> Response {
+    let mut robots = "User-agent: *\nAllow: /\n".to_string();
+    if let Some(base) = sitemap_base {
+        let _ = writeln!(robots, "\nSitemap: {base}/sitemap.xml");
+    }
+    text(robots, "text/plain; charset=utf-8")
+}
+
+/// `sitemap.xml` of the HTML pages of `dir`, `base` being the scheme and host of the site
+pub fn sitemap(
+    dir: &Path,
+    base: &str,
+    index: &str,
+    clean_urls: bool,
+    hide_dotfiles: bool,
+) -> Response {
+    let mut pages = Vec::new();
+    walk(dir, "/", index, clean_urls, hide_dotfiles, &mut pages);
+    pages.sort();
+    pages.truncate(SITEMAP_MAX_URLS);
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for page in pages {
+        let _ = writeln!(
+            xml,
+            "  <url><loc>{}{}</loc></url>",
+            escape(base),
+            escape(&page)
+        );
+    }
+    xml.push_str("</urlset>\n");
+    text(xml, "application/xml; charset=utf-8")
+}
+
+/// Collects the URL paths of the pages below `dir`, found at `url_path`, symlinks left out
+fn walk(
+    dir: &Path,
+    url_path: &str,
+    index: &str,
+    clean_urls: bool,
+    hide_dotfiles: bool,
+    pages: &mut Vec<String>,
+) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if hide_dotfiles && name.starts_with('.') {
+            continue;
+        }
+        let segment = utf8_percent_encode(&name, SEGMENT).to_string();
+        if file_type.is_dir() {
+            let url_path = format!("{url_path}{segment}/");
+            walk(
+                &entry.path(),
+                &url_path,
+                index,
+                clean_urls,
+                hide_dotfiles,
+                pages,
+            );
+        } else if file_type.is_file() && name == index {
+            pages.push(url_path.to_string());
+        } else if file_type.is_file() && name.ends_with(".html") {
+            let segment = if clean_urls {
+                segment.trim_end_matches(".html")
+            } else {
+                &segment
+            };
+            pages.push(format!("{url_path}{segment}"));
+        }
+    }
+}
+
+fn text(body: String, content_type: &'static str) -> Response {
+    (
+        [(header::CONTENT_TYPE, HeaderValue::from_static(content_type))],
+        body,
+    )
+        .into_response()
+}
+
+#[test]
+fn test_sitemap_pages() {
+    let dir = tempfile::tempdir().unwrap();
+    for path in ["docs/.drafts", "assets"] {
+        std::fs::create_dir_all(dir.path().join(path)).unwrap();
+    }
+    for path in [
+        "index.html",
+        "about us.html",
+        "docs/index.html",
+        "docs/setup.html",
+        "docs/.drafts/wip.html",
+        "assets/app.js",
+    ] {
+        std::fs::write(dir.path().join(path), "").unwrap();
+    }
+    let pages = |clean_urls, hide_dotfiles| {
+        let mut pages = Vec::new();
+        walk(
+            dir.path(),
+            "/",
+            "index.html",
+            clean_urls,
+            hide_dotfiles,
+            &mut pages,
+        );
+        pages.sort();
+        pages
+    };
+
+    assert_eq!(
+        vec!["/", "/about%20us.html", "/docs/", "/docs/setup.html"],
+        pages(false, true)
+    );
+    assert_eq!(
+        vec![
+            "/",
+            "/about%20us",
+            "/docs/",
+            "/docs/.drafts/wip",
+            "/docs/setup"
+        ],
+        pages(true, false)
+    );
+}
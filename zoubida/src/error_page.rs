@@ -0,0 +1,38 @@
+use std::path::Path;
+
+use axum::body::BoxBody;
+use axum::http::{Response, StatusCode};
+use axum::response::{Html, IntoResponse};
+
+/// Renders `status` with `<code>.html` from `pages_dir` when present, plain text otherwise
+pub async fn render(pages_dir: Option<&Path>, status: StatusCode) -> Response<BoxBody> {
+    if let Some(pages_dir) = pages_dir {
+        let page = pages_dir.join(format!("{}.html", status.as_u16()));
+        match tokio::fs::read_to_string(&page).await {
+            Ok(html) => return (status, Html(html)).into_response(),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => tracing::warn!(%error, "unable to read error page {page:?}"),
+        }
+    }
+
+    let reason = status.canonical_reason().unwrap_or_default();
+    (status, reason).into_response()
+}
+
+#[tokio::test]
+async fn test_render() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("404.html"), "<h1>lost</h1>").unwrap();
+
+    let res = render(Some(dir.path()), StatusCode::NOT_FOUND).await;
+    assert_eq!(StatusCode::NOT_FOUND, res.status());
+    assert_eq!("text/html; charset=utf-8", res.headers()["content-type"]);
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    assert_eq!("<h1>lost</h1>", body);
+
+    let res = render(Some(dir.path()), StatusCode::INTERNAL_SERVER_ERROR).await;
+    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, res.status());
+    assert_eq!("text/plain; charset=utf-8", res.headers()["content-type"]);
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    assert_eq!("Internal Server Error", body);
+}
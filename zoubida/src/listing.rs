@@ -0,0 +1,166 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use axum::response::Html;
+use axum::Json;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::Serialize;
+
+/// Characters that must be escaped in a single path segment of an `href`
+pub const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+struct Entry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<std::time::SystemTime>,
+}
+
+/// Entry of the JSON listing, `modified` in seconds since the Unix epoch
+#[derive(Serialize)]
+pub struct JsonEntry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<u64>,
+}
+
+/// Entries of `dir`, directories first, dot-prefixed ones left out when `hide_dotfiles` is set
+async fn read_entries(dir: &Path, hide_dotfiles: bool) -> std::io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut read_dir = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = read_dir.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if hide_dotfiles && name.starts_with('.') {
+            continue;
+        }
+        let metadata = entry.metadata().await?;
+        entries.push(Entry {
+            name,
+            is_dir: metadata.is_dir(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        });
+    }
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    Ok(entries)
+}
+
+/// Renders an HTML listing of `dir`, `uri_path` being the (slash-terminated) request path
+///
+/// Dot-prefixed entries are left out when `hide_dotfiles` is set.
+pub async fn render(
+    dir: &Path,
+    uri_path: &str,
+    hide_dotfiles: bool,
+) -> std::io::Result<Html<String>> {
+    let entries = read_entries(dir, hide_dotfiles).await?;
+
+    let title = escape(uri_path);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {title}</title></head>\n<body>\n<h1>Index of {title}</h1>\n<table>\n<tr><th>Name</th><th>Size</th><th>Modified</th></tr>\n"
+    );
+    if uri_path != "/" {
+        html.push_str("<tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n");
+    }
+    for entry in &entries {
+        let slash = if entry.is_dir { "/" } else { "" };
+        let href = utf8_percent_encode(&entry.name, SEGMENT);
+        let name = escape(&entry.name);
+        let size = if entry.is_dir {
+            String::from("-")
+        } else {
+            human_size(entry.size)
+        };
+        let modified = entry
+            .modified
+            .map(httpdate::fmt_http_date)
+            .unwrap_or_default();
+        writeln!(
+            html,
+            "<tr><td><a href=\"{href}{slash}\">{name}{slash}</a></td><td>{size}</td><td>{modified}</td></tr>"
+        )
+        .unwrap();
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+
+    Ok(Html(html))
+}
+
+/// Lists `dir` as JSON for scripts, with the same entries as [`render`]
+pub async fn json(dir: &Path, hide_dotfiles: bool) -> std::io::Result<Json<Vec<JsonEntry>>> {
+    let entries = read_entries(dir, hide_dotfiles).await?;
+    let entries = entries
+        .into_iter()
+        .map(|entry| JsonEntry {
+            name: entry.name,
+            is_dir: entry.is_dir,
+            size: entry.size,
+            modified: entry
+                .modified
+                .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|modified| modified.as_secs()),
+        })
+        .collect();
+    Ok(Json(entries))
+}
+
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn human_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{size} B");
+    }
+    let mut size = size as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[tokio::test]
+async fn test_render_encodes_links() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("my file#1.txt"), "hello").unwrap();
+    std::fs::create_dir(dir.path().join("<sub>")).unwrap();
+
+    let Html(html) = render(dir.path(), "/downloads/", false).await.unwrap();
+    assert!(html.contains("<a href=\"my%20file%231.txt\">my file#1.txt</a>"));
+    assert!(html.contains("<a href=\"%3Csub%3E/\">&lt;sub&gt;/</a>"));
+    assert!(html.contains("<td>5 B</td>"));
+    assert!(html.contains("<a href=\"../\">"));
+}
+
+#[test]
+fn test_human_size() {
+    assert_eq!("0 B", human_size(0));
+    assert_eq!("1.0 KiB", human_size(1024));
+    assert_eq!("1.5 MiB", human_size(1024 * 1024 * 3 / 2));
+}
@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::State;
+use axum::http::uri::Authority;
+use axum::http::{header, Method, Request};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::{AppState, ServeMode};
+
+/// Label of the requests whose method or site is not worth a series of its own
+const OTHER: &str = "other";
+
+/// Upper bounds in seconds of the latency histogram buckets
+const BUCKETS: [f64; 10] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0, 5.0];
+
+/// Request counters and latency histograms, rendered in Prometheus text format
+#[derive(Default)]
+pub struct Metrics {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// keyed by (method, status, site)
+    requests: BTreeMap<(String, u16, String), u64>,
+    /// keyed by method
+    durations: BTreeMap<String, Histogram>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Metrics {
+    pub fn record(&self, method: &str, status: u16, site: &str, elapsed: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        *inner
+            .requests
+            .entry((method.to_string(), status, site.to_string()))
+            .or_default() += 1;
+
+        let seconds = elapsed.as_secs_f64();
+        let histogram = inner.durations.entry(method.to_string()).or_default();
+        for (bucket, le) in histogram.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= le {
+                *bucket += 1;
+            }
+        }
+        histogram.sum += seconds;
+        histogram.count += 1;
+    }
+
+    pub fn render(&self) -> String {
+        let inner = self.inner.lock().unwrap();
+        let mut out = String::new();
+
+        out.push_str("# HELP zoubida_requests_total Total number of HTTP requests.\n");
+        out.push_str("# TYPE zoubida_requests_total counter\n");
+        for ((method, status, site), count) in &inner.requests {
+            let method = escape(method);
+            if site.is_empty() {
+                writeln!(
+                    out,
+                    "zoubida_requests_total{{method=\"{method}\",status=\"{status}\"}} {count}"
+                )
+                .unwrap();
+            } else {
+                let site = escape(site);
+                writeln!(
+                    out,
+                    "zoubida_requests_total{{method=\"{method}\",status=\"{status}\",site=\"{site}\"}} {count}"
+                )
+                .unwrap();
+            }
+        }
+
+        out.push_str("# HELP zoubida_request_duration_seconds HTTP request latency.\n");
+        out.push_str("# TYPE zoubida_request_duration_seconds histogram\n");
+        for (method, histogram) in &inner.durations {
+            let method = escape(method);
+            for (count, le) in histogram.buckets.iter().zip(BUCKETS) {
+                writeln!(
+                    out,
+                    "zoubida_request_duration_seconds_bucket{{method=\"{method}\",le=\"{le}\"}} {count}"
+                )
+                .unwrap();
+            }
+            let Histogram { sum, count, .. } = histogram;
+            writeln!(
+                out,
+                "zoubida_request_duration_seconds_bucket{{method=\"{method}\",le=\"+Inf\"}} {count}"
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "zoubida_request_duration_seconds_sum{{method=\"{method}\"}} {sum}"
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "zoubida_request_duration_seconds_count{{method=\"{method}\"}} {count}"
+            )
+            .unwrap();
+        }
+
+        out
+    }
+}
+
+/// Records every request, labelled by site in SUBDOMAIN mode only
+///
+/// Requests of sites that do not exist, of malformed hosts, or of unknown methods end up in
+/// [`OTHER`], so that the clients cannot grow the number of series.
+pub async fn track<B>(
+    State(state): State<Arc<AppState>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let Some(metrics) = &state.metrics else {
+        return next.run(request).await;
+    };
+
+    let start = Instant::now();
+    let method = method_label(request.method());
+    let site = match state.mode {
+        ServeMode::Subdomain(_) => request
+            .headers()
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .and_then(|host| host.parse::<Authority>().ok())
+            .and_then(|authority| state.site_dir(authority.as_str()).ok())
+            .and_then(|dir| Some(dir.file_name()?.to_str()?.to_string()))
+            .unwrap_or_else(|| OTHER.to_string()),
+        ServeMode::Path(_) | ServeMode::File(_) => String::new(),
+    };
+
+    let response = next.run(request).await;
+    metrics.record(method, response.status().as_u16(), &site, start.elapsed());
+    response
+}
+
+pub async fn serve(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let body = state
+        .metrics
+        .as_ref()
+        .map(Metrics::render)
+        .unwrap_or_default();
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::HEAD => "HEAD",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::OPTIONS => "OPTIONS",
+        Method::PATCH => "PATCH",
+        Method::CONNECT => "CONNECT",
+        Method::TRACE => "TRACE",
+        _ => OTHER,
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[test]
+fn test_render() {
+    let metrics = Metrics::default();
+    metrics.record("GET", 200, "", Duration::from_millis(3));
+    metrics.record("GET", 200, "", Duration::from_millis(30));
+    metrics.record("GET", 404, "", Duration::from_secs(10));
+
+    let out = metrics.render();
+    assert!(out.contains("zoubida_requests_total{method=\"GET\",status=\"200\"} 2\n"));
+    assert!(out.contains("zoubida_requests_total{method=\"GET\",status=\"404\"} 1\n"));
+    assert!(
+        out.contains("zoubida_request_duration_seconds_bucket{method=\"GET\",le=\"0.005\"} 1\n")
+    );
+    assert!(out.contains("zoubida_request_duration_seconds_bucket{method=\"GET\",le=\"0.05\"} 2\n"));
+    assert!(out.contains("zoubida_request_duration_seconds_bucket{method=\"GET\",le=\"+Inf\"} 3\n"));
+    assert!(out.contains("zoubida_request_duration_seconds_count{method=\"GET\"} 3\n"));
+}
+
+#[test]
+fn test_method_label() {
+    assert_eq!("PATCH", method_label(&Method::PATCH));
+    assert_eq!(OTHER, method_label(&Method::from_bytes(b"BREW").unwrap()));
+}
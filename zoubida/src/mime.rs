@@ -0,0 +1,203 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use axum::extract::State;
+use axum::http::{header, HeaderValue, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use crate::AppState;
+
+/// Characters escaped in the RFC 5987 `filename*` of a `Content-Disposition`
+const ATTR_CHAR: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
+/// Parses `<ext>=<type>` overrides, extensions being matched case-insensitively without their dot
+pub fn parse(values: &[String]) -> anyhow::Result<HashMap<String, HeaderValue>> {
+    let mut types = HashMap::new();
+    for value in values {
+        let Some((ext, mime)) = value.split_once('=') else {
+            bail!("invalid --mime {value:?}, expected \"<ext>=<type>\"");
+        };
+        let ext = ext.trim().trim_start_matches('.').to_ascii_lowercase();
+        if ext.is_empty() {
+            bail!("invalid --mime {value:?}, missing extension");
+        }
+        let mime = HeaderValue::from_str(mime.trim())
+            .with_context(|| format!("invalid --mime type {mime:?}"))?;
+        types.insert(ext, mime);
+    }
+    Ok(types)
+}
+
+/// Replaces the guessed `Content-Type` of successful responses for the configured extensions
+pub async fn override_content_type<B>(
+    State(state): State<Arc<AppState>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let mime = Path::new(request.uri().path())
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(|ext| state.mime_types.get(&ext.to_ascii_lowercase()))
+        .cloned();
+
+    let mut response = next.run(request).await;
+    if let Some(mime) = mime {
+        if response.status().is_success() {
+            response.headers_mut().insert(header::CONTENT_TYPE, mime);
+        }
+    }
+    response
+}
+
+/// Declares the text responses UTF-8 when their `Content-Type` has no charset
+pub async fn utf8_charset<B>(request: Request<B>, next: Next<B>) -> Response {
+    let mut response = next.run(request).await;
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(with_utf8_charset);
+    if let Some(content_type) = content_type {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, content_type);
+    }
+    response
+}
+
+/// `content_type` with `charset=utf-8` for text types lacking a charset, `None` for the others
+fn with_utf8_charset(content_type: &str) -> Option<HeaderValue> {
+    let essence = content_type.split(';').next()?.trim().to_ascii_lowercase();
+    let is_text = essence.starts_with("text/")
+        || essence == "application/json"
+        || essence == "application/javascript";
+    if !is_text || content_type.to_ascii_lowercase().contains("charset=") {
+        return None;
+    }
+    HeaderValue::from_str(&format!("{content_type}; charset=utf-8")).ok()
+}
+
+/// Lowercase extensions of `--download-types`, without their dot
+pub fn parse_download_types(values: &[String]) -> HashSet<String> {
+    values
+        .iter()
+        .map(|ext| ext.trim().trim_start_matches('.').to_ascii_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect()
+}
+
+/// Makes browsers download the files of the `--download-types` extensions instead of showing them
+pub async fn attachment<B>(
+    State(state): State<Arc<AppState>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let path = percent_decode_str(request.uri().path()).decode_utf8_lossy();
+    let file_name = path.rsplit('/').next().unwrap_or_default().to_string();
+    let download = Path::new(&file_name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| state.download_types.contains(&ext.to_ascii_lowercase()));
+
+    let mut response = next.run(request).await;
+    if download && response.status().is_success() {
+        if let Ok(disposition) = HeaderValue::from_str(&content_disposition(&file_name)) {
+            response
+                .headers_mut()
+                .insert(header::CONTENT_DISPOSITION, disposition);
+        }
+    }
+    response
+}
+
+/// `attachment` disposition of `file_name`, with an ASCII fallback for older clients
+fn content_disposition(file_name: &str) -> String {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            c if c.is_ascii() && !c.is_ascii_control() => c,
+            _ => '_',
+        })
+        .collect();
+    if fallback == file_name {
+        format!("attachment; filename=\"{file_name}\"")
+    } else {
+        let encoded = utf8_percent_encode(file_name, ATTR_CHAR);
+        format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+    }
+}
+
+#[test]
+fn test_parse() {
+    let types = parse(&[
+        "WASM=application/wasm".into(),
+        ".mjs = text/javascript".into(),
+    ])
+    .unwrap();
+    assert_eq!("application/wasm", types["wasm"]);
+    assert_eq!("text/javascript", types["mjs"]);
+
+    assert!(parse(&["wasm".into()]).is_err());
+    assert!(parse(&["=text/plain".into()]).is_err());
+    assert!(parse(&["txt=text/pl\u{1}ain".into()]).is_err());
+}
+
+#[test]
+fn test_content_disposition() {
+    assert_eq!(
+        "attachment; filename=\"report.csv\"",
+        content_disposition("report.csv")
+    );
+    assert_eq!(
+        "attachment; filename=\"r_sum_.csv\"; filename*=UTF-8''r%C3%A9sum%C3%A9.csv",
+        content_disposition("résumé.csv")
+    );
+    assert_eq!(
+        "attachment; filename=\"a_b_.csv\"; filename*=UTF-8''a%22b%5C.csv",
+        content_disposition("a\"b\\.csv")
+    );
+    assert_eq!(
+        HashSet::from(["csv".to_string(), "zip".to_string()]),
+        parse_download_types(&[".CSV".into(), "zip".into(), " ".into()])
+    );
+}
+
+#[test]
+fn test_with_utf8_charset() {
+    let charset = |content_type| {
+        with_utf8_charset(content_type).map(|value| value.to_str().unwrap().to_string())
+    };
+    assert_eq!(
+        Some("text/html; charset=utf-8".to_string()),
+        charset("text/html")
+    );
+    assert_eq!(
+        Some("application/javascript; charset=utf-8".to_string()),
+        charset("application/javascript")
+    );
+    assert_eq!(
+        Some("application/json; charset=utf-8".to_string()),
+        charset("application/json")
+    );
+    assert_eq!(None, charset("text/plain; charset=iso-8859-1"));
+    assert_eq!(None, charset("text/html; Charset=UTF-8"));
+    assert_eq!(None, charset("image/png"));
+    assert_eq!(None, charset("application/octet-stream"));
+}
@@ -0,0 +1,74 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use axum::extract::{ConnectInfo, State};
+use axum::http::{HeaderMap, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::AppState;
+
+/// IP of the client, as seen by the socket or reported by the proxy with `--behind-proxy`
+#[derive(Clone, Copy, Debug)]
+pub struct ClientIp(pub IpAddr);
+
+/// Stores the [`ClientIp`] of the request in its extensions
+pub async fn client_ip<B>(
+    State(state): State<Arc<AppState>>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let forwarded = state
+        .behind_proxy
+        .then(|| forwarded_for(request.headers()))
+        .flatten();
+    let ip = forwarded.or_else(|| {
+        request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+    });
+    if let Some(ip) = ip {
+        request.extensions_mut().insert(ClientIp(ip));
+    }
+    next.run(request).await
+}
+
+/// Last address of `X-Forwarded-For`, the one appended by the proxy itself
+///
+/// Earlier addresses come from the client and cannot be trusted.
+fn forwarded_for(headers: &HeaderMap) -> Option<IpAddr> {
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .last()
+        .and_then(|ip| ip.trim().parse().ok())
+}
+
+/// Whether the proxy received the request over HTTPS
+pub fn forwarded_https(headers: &HeaderMap) -> bool {
+    headers
+        .get("x-forwarded-proto")
+        .and_then(|proto| proto.to_str().ok())
+        .is_some_and(|proto| proto.trim().eq_ignore_ascii_case("https"))
+}
+
+#[test]
+fn test_forwarded_headers() {
+    let mut headers = HeaderMap::new();
+    assert_eq!(None, forwarded_for(&headers));
+    assert!(!forwarded_https(&headers));
+
+    headers.append("x-forwarded-for", "6.6.6.6, 203.0.113.7".parse().unwrap());
+    assert_eq!(
+        Some(IpAddr::from([203, 0, 113, 7])),
+        forwarded_for(&headers)
+    );
+    headers.append("x-forwarded-for", "2001:db8::1".parse().unwrap());
+    assert_eq!("2001:db8::1".parse().ok(), forwarded_for(&headers));
+
+    headers.insert("x-forwarded-proto", "HTTPS".parse().unwrap());
+    assert!(forwarded_https(&headers));
+}
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::{header, HeaderValue, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::AppState;
+
+/// Whether the request `path` matches `glob`
+///
+/// `*` matches any characters but `/` and `?` any one character. A glob without `/` is matched
+/// against the file name only, like `*.zip`, others against the whole path, like `/downloads/*`.
+pub fn matches(glob: &str, path: &str) -> bool {
+    let path = if glob.contains('/') {
+        path.trim_start_matches('/')
+    } else {
+        path.rsplit('/').next().unwrap_or(path)
+    };
+    matches_bytes(glob.trim_start_matches('/').as_bytes(), path.as_bytes())
+}
+
+fn matches_bytes(glob: &[u8], path: &[u8]) -> bool {
+    match (glob.first(), path.first()) {
+        (None, _) => path.is_empty(),
+        (Some(b'*'), _) => {
+            matches_bytes(&glob[1..], path)
+                || path.first().is_some_and(|c| *c != b'/') && matches_bytes(glob, &path[1..])
+        }
+        (Some(b'?'), Some(c)) if *c != b'/' => matches_bytes(&glob[1..], &path[1..]),
+        (Some(g), Some(c)) if g == c => matches_bytes(&glob[1..], &path[1..]),
+        _ => false,
+    }
+}
+
+/// Serves the files matching `--no-ranges` whole, ignoring their `Range` header
+pub async fn disable<B>(
+    State(state): State<Arc<AppState>>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    // globs name the files on disk, like "/Release Notes/*"
+    let path = percent_encoding::percent_decode_str(request.uri().path()).decode_utf8_lossy();
+    if !state.no_ranges.iter().any(|glob| matches(glob, &path)) {
+        return next.run(request).await;
+    }
+
+    request.headers_mut().remove(header::RANGE);
+    request.headers_mut().remove(header::IF_RANGE);
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(header::ACCEPT_RANGES, HeaderValue::from_static("none"));
+    response
+}
+
+#[test]
+fn test_matches() {
+    assert!(matches("*.zip", "/release.zip"));
+    assert!(matches("*.zip", "/downloads/v1/release.zip"));
+    assert!(!matches("*.zip", "/release.zip.sig"));
+    assert!(!matches("*.zip", "/zip"));
+    assert!(matches("release-?.zip", "/release-1.zip"));
+    assert!(!matches("release-?.zip", "/release-10.zip"));
+
+    assert!(matches("/downloads/*", "/downloads/a.tar"));
+    assert!(matches("downloads/*", "/downloads/a.tar"));
+    assert!(!matches("/downloads/*", "/downloads/v1/a.tar"));
+    assert!(matches("/downloads/*/*.tar", "/downloads/v1/a.tar"));
+    assert!(!matches("/downloads/*", "/videos/a.mp4"));
+    assert!(matches("*", "/"));
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use axum::extract::State;
+use axum::http::{header, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::proxy::ClientIp;
+use crate::AppState;
+
+/// Above this many tracked clients, those with a full bucket are forgotten, then the least recently seen
+const MAX_CLIENTS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+/// Token bucket per client IP, refilled at `rate` tokens per second up to a burst of `rate`
+pub struct RateLimiter {
+    rate: f64,
+    max_clients: usize,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: u32) -> Self {
+        Self {
+            rate: f64::from(rate),
+            max_clients: MAX_CLIENTS,
+            buckets: Mutex::default(),
+        }
+    }
+
+    /// Takes a token for `ip`, `false` when it has none left
+    fn allow(&self, ip: IpAddr, now: Instant) -> bool {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= self.max_clients && !buckets.contains_key(&ip) {
+            let rate = self.rate;
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.last).as_secs_f64() * rate < rate
+            });
+            // every client is busy, the map must not grow anyway
+            if buckets.len() >= self.max_clients {
+                let least_recent = buckets
+                    .iter()
+                    .min_by_key(|(_, bucket)| bucket.last)
+                    .map(|(ip, _)| *ip);
+                if let Some(least_recent) = least_recent {
+                    buckets.remove(&least_recent);
+                }
+            }
+        }
+
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: self.rate,
+            last: now,
+        });
+        let elapsed = now.duration_since(bucket.last).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.rate);
+        bucket.last = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Answers `429` to clients exceeding `--rate-limit`
+pub async fn limit<B>(
+    State(state): State<Arc<AppState>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let Some(limiter) = &state.rate_limit else {
+        return next.run(request).await;
+    };
+    let ip = request
+        .extensions()
+        .get::<ClientIp>()
+        .map(|ClientIp(ip)| *ip);
+
+    match ip {
+        Some(ip) if !limiter.allow(ip, Instant::now()) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, "1")],
+            "Too Many Requests",
+        )
+            .into_response(),
+        _ => next.run(request).await,
+    }
+}
+
+#[test]
+fn test_allow() {
+    let limiter = RateLimiter::new(2);
+    let (a, b) = (IpAddr::from([10, 0, 0, 1]), IpAddr::from([10, 0, 0, 2]));
+    let now = Instant::now();
+
+    assert!(limiter.allow(a, now));
+    assert!(limiter.allow(a, now));
+    assert!(!limiter.allow(a, now));
+    assert!(limiter.allow(b, now));
+
+    // refilled at 2 tokens per second
+    let later = now + std::time::Duration::from_millis(500);
+    assert!(limiter.allow(a, later));
+    assert!(!limiter.allow(a, later));
+}
+
+#[test]
+fn test_max_clients() {
+    let limiter = RateLimiter {
+        max_clients: 2,
+        ..RateLimiter::new(1)
+    };
+    let (a, b, c) = (
+        IpAddr::from([10, 0, 0, 1]),
+        IpAddr::from([10, 0, 0, 2]),
+        IpAddr::from([10, 0, 0, 3]),
+    );
+    let now = Instant::now();
+    let later = now + std::time::Duration::from_millis(100);
+
+    // none of the buckets is full again, the least recently seen client is evicted
+    assert!(limiter.allow(a, now));
+    assert!(limiter.allow(b, later));
+    assert!(limiter.allow(c, later));
+    let buckets = limiter.buckets.lock().unwrap();
+    assert_eq!(2, buckets.len());
+    assert!(!buckets.contains_key(&a));
+}
@@ -0,0 +1,79 @@
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+/// File holding the PID of the server while it is listening, removed when dropped
+pub struct PidFile(PathBuf);
+
+impl PidFile {
+    pub fn write(path: &Path) -> anyhow::Result<Self> {
+        std::fs::write(path, format!("{}\n", std::process::id()))
+            .with_context(|| format!("unable to write the PID file {path:?}"))?;
+        Ok(Self(path.to_path_buf()))
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(error) = std::fs::remove_file(&self.0) {
+            tracing::warn!("unable to remove the PID file {:?}: {error}", self.0);
+        }
+    }
+}
+
+/// Tells systemd the server is ready, when run as a `Type=notify` service
+pub fn notify_systemd() {
+    let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    if let Err(error) = notify(Path::new(&socket), "READY=1") {
+        tracing::warn!("unable to notify systemd on {socket:?}: {error}");
+    }
+}
+
+/// Sends `state` to the `sd_notify` socket at `path`, `@` prefixing an abstract socket name
+fn notify(path: &Path, state: &str) -> std::io::Result<()> {
+    let socket = UnixDatagram::unbound()?;
+    match path.to_str().and_then(|path| path.strip_prefix('@')) {
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &addr)?;
+        }
+        None => {
+            socket.send_to(state.as_bytes(), path)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_pid_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("zoubida.pid");
+
+    let pid_file = PidFile::write(&path).unwrap();
+    assert_eq!(
+        format!("{}\n", std::process::id()),
+        std::fs::read_to_string(&path).unwrap()
+    );
+    drop(pid_file);
+    assert!(!path.exists());
+
+    assert!(PidFile::write(&dir.path().join("missing/zoubida.pid")).is_err());
+}
+
+#[test]
+fn test_notify() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("notify");
+    let systemd = UnixDatagram::bind(&path).unwrap();
+
+    notify(&path, "READY=1").unwrap();
+    let mut buf = [0; 16];
+    let len = systemd.recv(&mut buf).unwrap();
+    assert_eq!(b"READY=1", &buf[..len]);
+
+    assert!(notify(&dir.path().join("missing"), "READY=1").is_err());
+}
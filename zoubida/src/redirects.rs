@@ -0,0 +1,154 @@
+use anyhow::{bail, Context};
+use axum::http::StatusCode;
+
+/// Name of the redirect map, looked up at the root of each site
+pub const REDIRECTS_FILE: &str = "_redirects";
+
+struct Rule {
+    /// exact path, or prefix up to and including the `/` of a `/*` splat
+    from: String,
+    splat: bool,
+    to: String,
+    status: StatusCode,
+}
+
+/// What to do with a request matching a rule
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    Redirect(StatusCode, String),
+    /// serve this path instead, without the client knowing
+    Rewrite(String),
+}
+
+/// Rules of a Netlify-style `_redirects` file, the first matching one applying
+#[derive(Default)]
+pub struct Redirects {
+    rules: Vec<Rule>,
+}
+
+impl Redirects {
+    /// Parses lines of `<from> <to> [status]`, `from` possibly ending in a `/*` splat that `:splat`
+    /// substitutes in `to`, status defaulting to `301` and `200` meaning a rewrite
+    pub fn parse(content: &str) -> anyhow::Result<Self> {
+        let mut rules = Vec::new();
+        for (n, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (from, to, status) = match fields[..] {
+                [from, to] => (from, to, StatusCode::MOVED_PERMANENTLY),
+                [from, to, status] => {
+                    let status = status
+                        .trim_end_matches('!')
+                        .parse::<u16>()
+                        .ok()
+                        .and_then(|status| StatusCode::from_u16(status).ok())
+                        .with_context(|| format!("invalid status on line {}", n + 1))?;
+                    (from, to, status)
+                }
+                _ => bail!("invalid line {}, expected \"<from> <to> [status]\"", n + 1),
+            };
+
+            if !from.starts_with('/') {
+                bail!("invalid line {}, paths must start with '/'", n + 1);
+            }
+            match status.as_u16() {
+                200 if !to.starts_with('/') => {
+                    bail!("invalid line {}, rewrites must target a path", n + 1)
+                }
+                200 | 301 | 302 | 303 | 307 | 308 => {}
+                _ => bail!("unsupported status {status} on line {}", n + 1),
+            }
+
+            let (from, splat) = match from.strip_suffix('*') {
+                Some(prefix) if prefix.ends_with('/') => (prefix.to_string(), true),
+                _ => (from.to_string(), false),
+            };
+            rules.push(Rule {
+                from,
+                splat,
+                to: to.to_string(),
+                status,
+            });
+        }
+        Ok(Self { rules })
+    }
+
+    /// Action of the first rule matching the request `path`
+    pub fn apply(&self, path: &str) -> Option<Action> {
+        let (rule, splat) = self.rules.iter().find_map(|rule| {
+            if !rule.splat {
+                return (rule.from == path).then_some((rule, ""));
+            }
+            if path == rule.from.trim_end_matches('/') {
+                return Some((rule, ""));
+            }
+            path.strip_prefix(&rule.from).map(|splat| (rule, splat))
+        })?;
+
+        let to = rule.to.replace(":splat", splat);
+        Some(match rule.status {
+            StatusCode::OK => Action::Rewrite(to),
+            status => Action::Redirect(status, to),
+        })
+    }
+}
+
+/// Carries the request query over to `to`, unless it has its own
+pub fn with_query(to: String, query: Option<&str>) -> String {
+    match query {
+        Some(query) if !to.contains('?') => format!("{to}?{query}"),
+        _ => to,
+    }
+}
+
+#[test]
+fn test_redirects() {
+    let redirects = Redirects::parse(
+        "# moved\n\
+         /old-page /new-page\n\
+         /docs /documentation 302\n\
+         /blog/* https://blog.example.com/:splat 301!\n\
+         /app/* /index.html 200\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        Some(Action::Redirect(
+            StatusCode::MOVED_PERMANENTLY,
+            "/new-page".into()
+        )),
+        redirects.apply("/old-page")
+    );
+    assert_eq!(
+        Some(Action::Redirect(StatusCode::FOUND, "/documentation".into())),
+        redirects.apply("/docs")
+    );
+    assert_eq!(None, redirects.apply("/docs/intro"));
+    assert_eq!(
+        Some(Action::Redirect(
+            StatusCode::MOVED_PERMANENTLY,
+            "https://blog.example.com/2023/hello".into()
+        )),
+        redirects.apply("/blog/2023/hello")
+    );
+    assert_eq!(
+        Some(Action::Redirect(
+            StatusCode::MOVED_PERMANENTLY,
+            "https://blog.example.com/".into()
+        )),
+        redirects.apply("/blog")
+    );
+    assert_eq!(
+        Some(Action::Rewrite("/index.html".into())),
+        redirects.apply("/app/settings")
+    );
+    assert_eq!(None, redirects.apply("/application"));
+
+    assert!(Redirects::parse("/a").is_err());
+    assert!(Redirects::parse("a /b").is_err());
+    assert!(Redirects::parse("/a /b 404").is_err());
+    assert!(Redirects::parse("/a https://b.com 200").is_err());
+}
@@ -0,0 +1,192 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use axum::body::{Body, BoxBody, HttpBody};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::http::{header, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use tokio::sync::broadcast;
+
+use crate::AppState;
+
+/// Route of the WebSocket the injected script listens to
+pub const RELOAD_PATH: &str = "/__zoubida_reload";
+
+const SCRIPT: &str = concat!(
+    "<script>new WebSocket((location.protocol === \"https:\" ? \"wss://\" : \"ws://\") + ",
+    "location.host + \"/__zoubida_reload\").onmessage = () => location.reload();</script>"
+);
+
+/// Notifies connected browsers that the served files changed
+#[derive(Clone)]
+pub struct Reloader {
+    sender: broadcast::Sender<()>,
+}
+
+impl Default for Reloader {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(1).0,
+        }
+    }
+}
+
+/// Polls `dir` every `interval` and triggers a reload once changes settle
+///
+/// A change is only reported when the next poll sees no further change, so that saving several
+/// files at once reloads the page once.
+pub async fn watch(dir: PathBuf, reloader: Reloader, interval: Duration) {
+    let mut last = snapshot(dir.clone()).await;
+    let mut pending = false;
+    loop {
+        tokio::time::sleep(interval).await;
+        let current = snapshot(dir.clone()).await;
+        if current != last {
+            last = current;
+            pending = true;
+        } else if pending {
+            pending = false;
+            tracing::info!("files changed in {dir:?}, reloading browsers");
+            let _ = reloader.sender.send(());
+        }
+    }
+}
+
+/// Latest modification time and number of entries below `dir`
+async fn snapshot(dir: PathBuf) -> (Option<SystemTime>, usize) {
+    tokio::task::spawn_blocking(move || {
+        let mut snapshot = (None, 0);
+        walk(&dir, &mut snapshot);
+        snapshot
+    })
+    .await
+    .unwrap_or_default()
+}
+
+fn walk(dir: &Path, snapshot: &mut (Option<SystemTime>, usize)) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        snapshot.1 += 1;
+        snapshot.0 = snapshot.0.max(metadata.modified().ok());
+        if metadata.is_dir() {
+            walk(&entry.path(), snapshot);
+        }
+    }
+}
+
+/// Upgrades to the WebSocket sending `reload` on every change
+pub async fn serve(State(state): State<Arc<AppState>>, upgrade: WebSocketUpgrade) -> Response {
+    let Some(reloader) = &state.reload else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let changes = reloader.sender.subscribe();
+    upgrade.on_upgrade(|socket| notify(socket, changes))
+}
+
+async fn notify(mut socket: WebSocket, mut changes: broadcast::Receiver<()>) {
+    loop {
+        match changes.recv().await {
+            Ok(()) | Err(broadcast::error::RecvError::Lagged(_)) => {
+                if socket.send(Message::Text("reload".into())).await.is_err() {
+                    return;
+                }
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Appends the reload script to HTML pages
+///
+/// The pages are requested without `Accept-Encoding`, so that `--precompressed` serves them as-is
+/// rather than as `.br` or `.gz` files, `--compress` still compressing the result.
+pub async fn inject_script<B>(mut request: Request<B>, next: Next<B>) -> Response {
+    if is_page_request(&request) {
+        request.headers_mut().remove(header::ACCEPT_ENCODING);
+    }
+    let response = next.run(request).await;
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    // partial, not modified and encoded responses cannot be extended
+    if response.status() != StatusCode::OK
+        || !is_html
+        || response.headers().contains_key(header::CONTENT_ENCODING)
+    {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(error) => {
+            tracing::error!("unable to read page to inject reload script: {error}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let page = String::from_utf8_lossy(&body);
+    let page = match page.rfind("</body>") {
+        Some(end) => format!("{}{SCRIPT}{}", &page[..end], &page[end..]),
+        None => format!("{page}{SCRIPT}"),
+    };
+
+    // the validators and length are the ones of the file, not of the rewritten page
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::ACCEPT_RANGES);
+    parts.headers.remove(header::ETAG);
+    Response::from_parts(
+        parts,
+        BoxBody::new(Body::from(page).map_err(axum::Error::new)),
+    )
+}
+
+/// Whether `request` asks for an HTML page, by its `Accept` header or the extension of its path
+fn is_page_request<B>(request: &Request<B>) -> bool {
+    let accepts_html = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+    let path = request.uri().path();
+    let extension = Path::new(path)
+        .extension()
+        .map(|extension| extension.to_ascii_lowercase());
+    // paths without an extension may be directories or --clean-urls pages
+    accepts_html
+        || path.ends_with('/')
+        || extension.is_none_or(|extension| extension == "html" || extension == "htm")
+}
+
+#[tokio::test]
+async fn test_watch() {
+    let dir = tempfile::tempdir().unwrap();
+    let reloader = Reloader::default();
+    let mut changes = reloader.sender.subscribe();
+    tokio::spawn(watch(
+        dir.path().to_path_buf(),
+        reloader,
+        Duration::from_millis(20),
+    ));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // several writes in a row trigger a single reload
+    for n in 0..3 {
+        std::fs::write(dir.path().join(format!("{n}.html")), "hi").unwrap();
+    }
+    tokio::time::timeout(Duration::from_secs(2), changes.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(changes.try_recv().is_err());
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use anyhow::{bail, Context};
+use serde::Deserialize;
+
+use crate::auth::Credentials;
+
+/// Name of the per-site configuration file, looked up at the root of each SUBDOMAIN site
+pub const CONFIG_FILE: &str = "zoubida.toml";
+
+/// Per-site overrides of the global flags, every field falling back to its flag when absent
+#[derive(Default)]
+pub struct SiteConfig {
+    pub index: Option<String>,
+    pub spa: Option<bool>,
+    pub list_dir: Option<bool>,
+    pub auth: Option<Credentials>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawConfig {
+    index: Option<String>,
+    spa: Option<bool>,
+    list_dir: Option<bool>,
+    auth: Option<RawAuth>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawAuth {
+    user: String,
+    pass: String,
+}
+
+impl SiteConfig {
+    pub fn parse(content: &str) -> anyhow::Result<Self> {
+        let raw: RawConfig = toml::from_str(content)?;
+        if let Some(index) = &raw.index {
+            if !crate::is_plain_file_name(index) {
+                bail!("index must be a plain file name");
+            }
+        }
+        Ok(Self {
+            index: raw.index,
+            spa: raw.spa,
+            list_dir: raw.list_dir,
+            auth: raw.auth.map(|auth| Credentials::new(auth.user, auth.pass)),
+        })
+    }
+}
+
+/// Files read from the root of each site, parsed again only when their modification time changes
+pub struct SiteFiles<T> {
+    file_name: &'static str,
+    parse: fn(&str) -> anyhow::Result<T>,
+    entries: Mutex<HashMap<PathBuf, (SystemTime, Arc<T>)>>,
+}
+
+impl<T: Default> SiteFiles<T> {
+    pub fn new(file_name: &'static str, parse: fn(&str) -> anyhow::Result<T>) -> Self {
+        Self {
+            file_name,
+            parse,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Parsed file of the site in `site_dir`, the defaults when it has none or it is invalid
+    pub async fn get(&self, site_dir: &Path) -> Arc<T> {
+        let path = site_dir.join(self.file_name);
+        let Some(modified) = tokio::fs::metadata(&path)
+            .await
+            .ok()
+            .and_then(|metadata| metadata.modified().ok())
+        else {
+            self.entries.lock().unwrap().remove(site_dir);
+            return Arc::default();
+        };
+
+        if let Some((mtime, parsed)) = self.entries.lock().unwrap().get(site_dir) {
+            if *mtime == modified {
+                return parsed.clone();
+            }
+        }
+
+        let parsed = match self.load(&path).await {
+            Ok(parsed) => Arc::new(parsed),
+            Err(error) => {
+                tracing::warn!("ignoring {path:?}: {error:#}");
+                Arc::default()
+            }
+        };
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(site_dir.to_path_buf(), (modified, parsed.clone()));
+        parsed
+    }
+
+    async fn load(&self, path: &Path) -> anyhow::Result<T> {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .context("unable to read file")?;
+        (self.parse)(&content)
+    }
+}
+
+#[test]
+fn test_parse() {
+    let config = SiteConfig::parse(
+        "index = \"default.html\"\nspa = true\n\n[auth]\nuser = \"admin\"\npass = \"hunter2\"\n",
+    )
+    .unwrap();
+    assert_eq!(Some("default.html"), config.index.as_deref());
+    assert_eq!(Some(true), config.spa);
+    assert_eq!(None, config.list_dir);
+    assert!(config.auth.is_some());
+
+    assert!(SiteConfig::parse("spa = \"yes\"").is_err());
+    assert!(SiteConfig::parse("unknown = 1").is_err());
+    assert!(SiteConfig::parse("index = \"../secret\"").is_err());
+}
@@ -0,0 +1,673 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Context};
+use axum_server::tls_rustls::RustlsConfig;
+use clap::ValueEnum;
+use rustls::server::{ClientHello, ResolvesServerCert, WantsServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::{
+    Certificate, ConfigBuilder, PrivateKey, ServerConfig, SignatureScheme, SupportedProtocolVersion,
+};
+
+static TLS13_ONLY: &[&SupportedProtocolVersion] = &[&rustls::version::TLS13];
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum TlsVersion {
+    #[value(name = "1.2")]
+    V1_2,
+    #[value(name = "1.3")]
+    V1_3,
+}
+
+impl TlsVersion {
+    /// Protocol versions enabled when this is the minimum
+    pub fn versions(self) -> &'static [&'static SupportedProtocolVersion] {
+        match self {
+            TlsVersion::V1_2 => rustls::ALL_VERSIONS,
+            TlsVersion::V1_3 => TLS13_ONLY,
+        }
+    }
+}
+
+impl std::fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TlsVersion::V1_2 => write!(f, "TLS 1.2"),
+            TlsVersion::V1_3 => write!(f, "TLS 1.3"),
+        }
+    }
+}
+
+/// Files a TLS configuration is loaded from, and reloaded from by [`watch`]
+pub enum Source {
+    /// A PEM certificate chain and private key, see [`server_config`]
+    Pem {
+        cert: PathBuf,
+        key: PathBuf,
+        ocsp: Option<PathBuf>,
+    },
+    /// A directory of certificates selected by SNI, see [`sni_server_config`]
+    Dir {
+        dir: PathBuf,
+        default: Option<(PathBuf, PathBuf)>,
+        default_ocsp: Option<PathBuf>,
+        base_domain: Option<String>,
+    },
+}
+
+impl Source {
+    pub fn server_config(&self, min_version: TlsVersion) -> anyhow::Result<ServerConfig> {
+        match self {
+            Source::Pem { cert, key, ocsp } => {
+                server_config(cert, key, ocsp.as_deref(), min_version)
+            }
+            Source::Dir {
+                dir,
+                default,
+                default_ocsp,
+                base_domain,
+            } => sni_server_config(
+                dir,
+                default
+                    .as_ref()
+                    .map(|(cert, key)| (cert.as_path(), key.as_path())),
+                default_ocsp.as_deref(),
+                base_domain.clone(),
+                min_version,
+            ),
+        }
+    }
+
+    /// Modification times of the files to load, `None` for the missing ones
+    async fn modified(&self) -> Vec<(PathBuf, Option<SystemTime>)> {
+        let files = match self {
+            Source::Pem { cert, key, ocsp } => {
+                let mut files = vec![cert.clone(), key.clone()];
+                files.extend(ocsp.clone());
+                files
+            }
+            Source::Dir {
+                dir,
+                default,
+                default_ocsp,
+                ..
+            } => {
+                let mut files = certificate_files(dir).await;
+                if let Some((cert, key)) = default {
+                    files.extend([cert.clone(), key.clone()]);
+                }
+                files.extend(default_ocsp.clone());
+                files
+            }
+        };
+
+        let mut modified = Vec::with_capacity(files.len());
+        for file in files {
+            let time = tokio::fs::metadata(&file)
+                .await
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            modified.push((file, time));
+        }
+        modified
+    }
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Pem { cert, .. } => write!(f, "{cert:?}"),
+            Source::Dir { dir, .. } => write!(f, "{dir:?}"),
+        }
+    }
+}
+
+/// Certificate, key and OCSP response files of `dir`, sorted so that two listings compare equal
+async fn certificate_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let Ok(mut entries) = tokio::fs::read_dir(dir).await {
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some("pem" | "key" | "ocsp")
+            ) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Server configuration for a PEM certificate chain and private key
+///
+/// The DER OCSP response in `ocsp` is stapled to the handshakes, unless it cannot be read.
+pub fn server_config(
+    cert: &Path,
+    key: &Path,
+    ocsp: Option<&Path>,
+    min_version: TlsVersion,
+) -> anyhow::Result<ServerConfig> {
+    let (certs, private_key) = read_pem(cert, key)?;
+    let ocsp = ocsp.and_then(read_ocsp).unwrap_or_default();
+    let mut config = builder(min_version)?
+        .with_single_cert_with_ocsp_and_sct(certs, private_key, ocsp, Vec::new())
+        .with_context(|| format!("invalid private key {key:?}"))?;
+    config.alpn_protocols = alpn_protocols();
+    Ok(config)
+}
+
+/// Server configuration picking the certificate of each subdomain from `dir` based on SNI
+///
+/// Certificates are named `<subdomain>.pem` with their key in `<subdomain>.key` and the optional OCSP
+/// response to staple in `<subdomain>.ocsp`, `@` being the apex domain. Hosts without a certificate
+/// of their own get `default` when given, stapled with `default_ocsp`.
+pub fn sni_server_config(
+    dir: &Path,
+    default: Option<(&Path, &Path)>,
+    default_ocsp: Option<&Path>,
+    base_domain: Option<String>,
+    min_version: TlsVersion,
+) -> anyhow::Result<ServerConfig> {
+    let mut certs = HashMap::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("unable to read {dir:?}"))? {
+        let cert = entry?.path();
+        if cert.extension().and_then(|ext| ext.to_str()) != Some("pem") {
+            continue;
+        }
+        let Some(subdomain) = cert.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let key = cert.with_extension("key");
+        let ocsp = Some(cert.with_extension("ocsp")).filter(|ocsp| ocsp.exists());
+        certs.insert(
+            subdomain.to_ascii_lowercase(),
+            certified_key(&cert, &key, ocsp.as_deref())?,
+        );
+    }
+    if certs.is_empty() && default.is_none() {
+        bail!("no certificate found in {dir:?}");
+    }
+
+    let default = default
+        .map(|(cert, key)| certified_key(cert, key, default_ocsp))
+        .transpose()?;
+    tracing::info!("loaded {} certificate(s) from {dir:?}", certs.len());
+
+    let resolver = SniResolver {
+        certs,
+        default,
+        base_domain,
+    };
+    let mut config = builder(min_version)?.with_cert_resolver(Arc::new(resolver));
+    config.alpn_protocols = alpn_protocols();
+    Ok(config)
+}
+
+struct SniResolver {
+    certs: HashMap<String, Arc<CertifiedKey>>,
+    default: Option<Arc<CertifiedKey>>,
+    base_domain: Option<String>,
+}
+
+impl SniResolver {
+    fn lookup(&self, server_name: Option<&str>) -> Option<Arc<CertifiedKey>> {
+        let cert = server_name.and_then(|name| {
+            let name = name.to_ascii_lowercase();
+            let subdomain = crate::subdomain(&name, self.base_domain.as_deref()).unwrap_or("@");
+            self.certs.get(subdomain).cloned()
+        });
+        cert.or_else(|| self.default.clone())
+    }
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        self.lookup(client_hello.server_name())
+    }
+}
+
+fn builder(
+    min_version: TlsVersion,
+) -> anyhow::Result<ConfigBuilder<ServerConfig, WantsServerCert>> {
+    Ok(ServerConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(min_version.versions())?
+        .with_no_client_auth())
+}
+
+/// HTTP/2 when the client supports it, HTTP/1.1 otherwise
+pub fn alpn_protocols() -> Vec<Vec<u8>> {
+    vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+}
+
+fn certified_key(
+    cert: &Path,
+    key: &Path,
+    ocsp: Option<&Path>,
+) -> anyhow::Result<Arc<CertifiedKey>> {
+    let (certs, private_key) = read_pem(cert, key)?;
+    let signing_key = rustls::sign::any_supported_type(&private_key)
+        .map_err(|_| anyhow::anyhow!("unsupported private key {key:?}"))?;
+    let mut certified = CertifiedKey::new(certs, signing_key);
+    certified.ocsp = ocsp.and_then(read_ocsp);
+    Ok(Arc::new(certified))
+}
+
+/// Reads a DER OCSP response, `None` to serve without stapling when it is unusable
+fn read_ocsp(path: &Path) -> Option<Vec<u8>> {
+    match std::fs::read(path) {
+        // a DER response is an ASN.1 SEQUENCE
+        Ok(der) if der.first() == Some(&0x30) => Some(der),
+        Ok(_) => {
+            tracing::warn!("{path:?} is not a DER OCSP response, serving without stapling");
+            None
+        }
+        Err(error) => {
+            tracing::warn!("unable to read {path:?}, serving without stapling: {error}");
+            None
+        }
+    }
+}
+
+fn read_pem(cert: &Path, key: &Path) -> anyhow::Result<(Vec<Certificate>, PrivateKey)> {
+    let certs = std::fs::read(cert).with_context(|| format!("unable to read {cert:?}"))?;
+    let certs = rustls_pemfile::certs(&mut certs.as_slice())
+        .with_context(|| format!("invalid certificate {cert:?}"))?;
+    if certs.is_empty() {
+        bail!("no certificate found in {cert:?}");
+    }
+
+    let key_pem = std::fs::read(key).with_context(|| format!("unable to read {key:?}"))?;
+    let key_der = match rustls_pemfile::read_one(&mut key_pem.as_slice())
+        .with_context(|| format!("invalid private key {key:?}"))?
+    {
+        Some(rustls_pemfile::Item::RSAKey(der))
+        | Some(rustls_pemfile::Item::PKCS8Key(der))
+        | Some(rustls_pemfile::Item::ECKey(der)) => der,
+        _ => bail!("no supported private key found in {key:?}"),
+    };
+
+    let certs: Vec<_> = certs.into_iter().map(Certificate).collect();
+    let private_key = PrivateKey(key_der);
+    check_key_pair(&certs[0], &private_key)
+        .with_context(|| format!("{key:?} is not the private key of {cert:?}"))?;
+    Ok((certs, private_key))
+}
+
+/// Signs a message with `key` and verifies it with the public key of `cert`
+fn check_key_pair(cert: &Certificate, key: &PrivateKey) -> anyhow::Result<()> {
+    const SCHEMES: &[(SignatureScheme, &webpki::SignatureAlgorithm)] = &[
+        (
+            SignatureScheme::ECDSA_NISTP256_SHA256,
+            &webpki::ECDSA_P256_SHA256,
+        ),
+        (
+            SignatureScheme::ECDSA_NISTP384_SHA384,
+            &webpki::ECDSA_P384_SHA384,
+        ),
+        (SignatureScheme::ED25519, &webpki::ED25519),
+        (
+            SignatureScheme::RSA_PKCS1_SHA256,
+            &webpki::RSA_PKCS1_2048_8192_SHA256,
+        ),
+    ];
+    const MESSAGE: &[u8] = b"zoubida key pair check";
+
+    let signing_key = rustls::sign::any_supported_type(key)
+        .map_err(|_| anyhow::anyhow!("unsupported private key"))?;
+    let schemes: Vec<_> = SCHEMES.iter().map(|(scheme, _)| *scheme).collect();
+    let signer = signing_key
+        .choose_scheme(&schemes)
+        .context("unsupported private key type")?;
+    let signature = signer.sign(MESSAGE)?;
+    let (_, algorithm) = SCHEMES
+        .iter()
+        .find(|(scheme, _)| *scheme == signer.scheme())
+        .unwrap_or_else(|| unreachable!());
+
+    let cert = webpki::EndEntityCert::try_from(cert.0.as_slice())
+        .map_err(|error| anyhow::anyhow!("invalid certificate: {error}"))?;
+    cert.verify_signature(algorithm, MESSAGE, &signature)
+        .map_err(|_| anyhow::anyhow!("the public key of the certificate does not match"))
+}
+
+/// Reloads `config` in place whenever any of the files of `source` changes
+///
+/// Files are polled every `interval` and only reloaded once unchanged for a whole interval, so that
+/// a renewal caught midway is not loaded. The current certificates are kept when the new ones are invalid.
+pub async fn watch(
+    config: RustlsConfig,
+    source: Source,
+    min_version: TlsVersion,
+    interval: Duration,
+) {
+    let mut loaded = source.modified().await;
+    let mut last = loaded.clone();
+    let mut ticks = tokio::time::interval(interval);
+    loop {
+        ticks.tick().await;
+        let current = source.modified().await;
+        if current != last {
+            last = current;
+            continue;
+        }
+        if current == loaded {
+            continue;
+        }
+        loaded = current;
+
+        match source.server_config(min_version) {
+            Ok(new_config) => {
+                config.reload_from_config(Arc::new(new_config));
+                tracing::info!("reloaded TLS certificates from {source}");
+            }
+            Err(error) => tracing::error!("keeping the current TLS certificates: {error:#}"),
+        }
+    }
+}
+
+#[cfg(test)]
+fn write_self_signed(cert: &Path, key: &Path) {
+    let generated = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    std::fs::write(cert, generated.serialize_pem().unwrap()).unwrap();
+    std::fs::write(key, generated.serialize_private_key_pem()).unwrap();
+}
+
+#[test]
+fn test_server_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let (cert_path, key_path) = (dir.path().join("cert.pem"), dir.path().join("key.pem"));
+    write_self_signed(&cert_path, &key_path);
+
+    assert!(server_config(&cert_path, &key_path, None, TlsVersion::V1_2).is_ok());
+    assert!(server_config(&cert_path, &key_path, None, TlsVersion::V1_3).is_ok());
+    assert!(server_config(&cert_path, &cert_path, None, TlsVersion::V1_3).is_err());
+    assert!(server_config(&key_path, &key_path, None, TlsVersion::V1_3).is_err());
+}
+
+#[tokio::test]
+async fn test_watch() {
+    let dir = tempfile::tempdir().unwrap();
+    let (cert, key) = (dir.path().join("cert.pem"), dir.path().join("key.pem"));
+    write_self_signed(&cert, &key);
+
+    let source = Source::Pem {
+        cert: cert.clone(),
+        key: key.clone(),
+        ocsp: None,
+    };
+    let config =
+        RustlsConfig::from_config(Arc::new(source.server_config(TlsVersion::V1_2).unwrap()));
+    let initial = config.get_inner();
+    tokio::spawn(watch(
+        config.clone(),
+        source,
+        TlsVersion::V1_2,
+        Duration::from_millis(20),
+    ));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // a broken renewal keeps the current certificate
+    std::fs::write(&cert, "garbage").unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(Arc::ptr_eq(&initial, &config.get_inner()));
+
+    write_self_signed(&cert, &key);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!Arc::ptr_eq(&initial, &config.get_inner()));
+}
+
+#[test]
+fn test_sni_resolver() {
+    let dir = tempfile::tempdir().unwrap();
+    let load = |name: &str| {
+        let (cert, key) = (
+            dir.path().join(format!("{name}.pem")),
+            dir.path().join(format!("{name}.key")),
+        );
+        write_self_signed(&cert, &key);
+        certified_key(&cert, &key, None).unwrap()
+    };
+    let (blog, apex) = (load("blog"), load("@"));
+
+    let mut resolver = SniResolver {
+        certs: HashMap::from([
+            ("blog".to_string(), blog.clone()),
+            ("@".to_string(), apex.clone()),
+        ]),
+        default: None,
+        base_domain: Some("braindead.fr".to_string()),
+    };
+    let is = |cert: Option<Arc<CertifiedKey>>, expected: &Arc<CertifiedKey>| {
+        cert.is_some_and(|cert| Arc::ptr_eq(&cert, expected))
+    };
+    assert!(is(resolver.lookup(Some("Blog.braindead.fr")), &blog));
+    assert!(is(resolver.lookup(Some("braindead.fr")), &apex));
+    assert!(resolver.lookup(Some("shop.braindead.fr")).is_none());
+    assert!(resolver.lookup(None).is_none());
+
+    resolver.default = Some(apex.clone());
+    assert!(is(resolver.lookup(Some("shop.braindead.fr")), &apex));
+    assert!(is(resolver.lookup(None), &apex));
+
+    assert!(sni_server_config(dir.path(), None, None, None, TlsVersion::V1_2).is_ok());
+    std::fs::write(dir.path().join("broken.pem"), "garbage").unwrap();
+    assert!(sni_server_config(dir.path(), None, None, None, TlsVersion::V1_2).is_err());
+}
+
+#[test]
+fn test_ocsp() {
+    let dir = tempfile::tempdir().unwrap();
+    let (cert, key) = (dir.path().join("blog.pem"), dir.path().join("blog.key"));
+    let ocsp = dir.path().join("blog.ocsp");
+    write_self_signed(&cert, &key);
+
+    // failing to staple never fails the handshakes
+    assert_eq!(None, read_ocsp(&ocsp));
+    assert!(server_config(&cert, &key, Some(&ocsp), TlsVersion::V1_2).is_ok());
+    std::fs::write(&ocsp, "garbage").unwrap();
+    assert_eq!(None, read_ocsp(&ocsp));
+    assert!(certified_key(&cert, &key, Some(&ocsp))
+        .unwrap()
+        .ocsp
+        .is_none());
+
+    std::fs::write(&ocsp, [0x30, 0x03, 0x0a, 0x01, 0x00]).unwrap();
+    assert_eq!(Some(vec![0x30, 0x03, 0x0a, 0x01, 0x00]), read_ocsp(&ocsp));
+    assert!(server_config(&cert, &key, Some(&ocsp), TlsVersion::V1_2).is_ok());
+    assert_eq!(
+        Some(vec![0x30, 0x03, 0x0a, 0x01, 0x00]),
+        certified_key(&cert, &key, Some(&ocsp)).unwrap().ocsp
+    );
+}
+
+#[test]
+fn test_mismatched_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let (cert, key) = (dir.path().join("cert.pem"), dir.path().join("key.pem"));
+    let other_key = dir.path().join("other.key");
+    write_self_signed(&cert, &key);
+    write_self_signed(&dir.path().join("other.pem"), &other_key);
+
+    assert!(read_pem(&cert, &key).is_ok());
+    let error = server_config(&cert, &other_key, None, TlsVersion::V1_2).unwrap_err();
+    assert_eq!(
+        format!("{other_key:?} is not the private key of {cert:?}"),
+        error.to_string()
+    );
+}
+
+/// Runs a handshake in memory, returning the client connection
+#[cfg(test)]
+fn handshake(
+    config: Arc<ServerConfig>,
+    client_config: rustls::ClientConfig,
+    server_name: &str,
+) -> rustls::ClientConnection {
+    let mut client =
+        rustls::ClientConnection::new(Arc::new(client_config), server_name.try_into().unwrap())
+            .unwrap();
+    let mut server = rustls::ServerConnection::new(config).unwrap();
+    while client.is_handshaking() || server.is_handshaking() {
+        let mut records = Vec::new();
+        client.write_tls(&mut records).unwrap();
+        server.read_tls(&mut records.as_slice()).unwrap();
+        server.process_new_packets().unwrap();
+        records.clear();
+        server.write_tls(&mut records).unwrap();
+        client.read_tls(&mut records.as_slice()).unwrap();
+        client.process_new_packets().unwrap();
+    }
+    client
+}
+
+/// OCSP response stapled by `config` to the handshakes for `server_name`
+#[cfg(test)]
+fn stapled(config: Arc<ServerConfig>, server_name: &str) -> Vec<u8> {
+    struct Staple(std::sync::Mutex<Vec<u8>>);
+
+    impl rustls::client::ServerCertVerifier for Staple {
+        fn verify_server_cert(
+            &self,
+            _end_entity: &Certificate,
+            _intermediates: &[Certificate],
+            _server_name: &rustls::ServerName,
+            _scts: &mut dyn Iterator<Item = &[u8]>,
+            ocsp_response: &[u8],
+            _now: SystemTime,
+        ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+            *self.0.lock().unwrap() = ocsp_response.to_vec();
+            Ok(rustls::client::ServerCertVerified::assertion())
+        }
+    }
+
+    let staple = Arc::new(Staple(Default::default()));
+    let client_config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(staple.clone())
+        .with_no_client_auth();
+    handshake(config, client_config, server_name);
+    let ocsp = staple.0.lock().unwrap().clone();
+    ocsp
+}
+
+#[test]
+fn test_alpn() {
+    let dir = tempfile::tempdir().unwrap();
+    let (cert, key) = (dir.path().join("cert.pem"), dir.path().join("key.pem"));
+    write_self_signed(&cert, &key);
+    let config = Arc::new(server_config(&cert, &key, None, TlsVersion::V1_2).unwrap());
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(&read_pem(&cert, &key).unwrap().0[0]).unwrap();
+    let negotiated = |protocols: &[&[u8]]| {
+        let mut client_config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots.clone())
+            .with_no_client_auth();
+        client_config.alpn_protocols = protocols.iter().map(|protocol| protocol.to_vec()).collect();
+        handshake(config.clone(), client_config, "localhost")
+            .alpn_protocol()
+            .map(<[u8]>::to_vec)
+    };
+
+    assert_eq!(Some(b"h2".to_vec()), negotiated(&[b"h2", b"http/1.1"]));
+    assert_eq!(Some(b"http/1.1".to_vec()), negotiated(&[b"http/1.1"]));
+    assert_eq!(None, negotiated(&[]));
+}
+
+#[tokio::test]
+async fn test_watch_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    let certs = dir.path().join("certs");
+    std::fs::create_dir(&certs).unwrap();
+    write_self_signed(&certs.join("blog.pem"), &certs.join("blog.key"));
+    let (cert, key) = (dir.path().join("cert.pem"), dir.path().join("key.pem"));
+    write_self_signed(&cert, &key);
+
+    let source = Source::Dir {
+        dir: certs.clone(),
+        default: Some((cert.clone(), key.clone())),
+        default_ocsp: None,
+        base_domain: None,
+    };
+    let config =
+        RustlsConfig::from_config(Arc::new(source.server_config(TlsVersion::V1_2).unwrap()));
+    tokio::spawn(watch(
+        config.clone(),
+        source,
+        TlsVersion::V1_2,
+        Duration::from_millis(20),
+    ));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // the default certificate is watched as well as the directory
+    let initial = config.get_inner();
+    write_self_signed(&cert, &key);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let renewed = config.get_inner();
+    assert!(!Arc::ptr_eq(&initial, &renewed));
+
+    write_self_signed(&certs.join("shop.pem"), &certs.join("shop.key"));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!Arc::ptr_eq(&renewed, &config.get_inner()));
+}
+
+#[tokio::test]
+async fn test_watch_ocsp() {
+    let dir = tempfile::tempdir().unwrap();
+    let certs = dir.path().join("certs");
+    std::fs::create_dir(&certs).unwrap();
+    write_self_signed(&certs.join("blog.pem"), &certs.join("blog.key"));
+    std::fs::write(certs.join("blog.ocsp"), [0x30, 0x03, 0x0a, 0x01, 0x00]).unwrap();
+    let (cert, key, ocsp) = (
+        dir.path().join("cert.pem"),
+        dir.path().join("key.pem"),
+        dir.path().join("cert.ocsp"),
+    );
+    write_self_signed(&cert, &key);
+    std::fs::write(&ocsp, [0x30, 0x03, 0x0a, 0x01, 0x01]).unwrap();
+
+    let source = Source::Dir {
+        dir: certs.clone(),
+        default: Some((cert, key)),
+        default_ocsp: Some(ocsp.clone()),
+        base_domain: Some("braindead.fr".to_string()),
+    };
+    let config =
+        RustlsConfig::from_config(Arc::new(source.server_config(TlsVersion::V1_2).unwrap()));
+    tokio::spawn(watch(
+        config.clone(),
+        source,
+        TlsVersion::V1_2,
+        Duration::from_millis(20),
+    ));
+    assert_eq!(
+        vec![0x30, 0x03, 0x0a, 0x01, 0x00],
+        stapled(config.get_inner(), "blog.braindead.fr")
+    );
+    // --ocsp-file staples the default certificate
+    assert_eq!(
+        vec![0x30, 0x03, 0x0a, 0x01, 0x01],
+        stapled(config.get_inner(), "shop.braindead.fr")
+    );
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    std::fs::write(certs.join("blog.ocsp"), [0x30, 0x03, 0x0a, 0x01, 0x02]).unwrap();
+    std::fs::write(&ocsp, [0x30, 0x03, 0x0a, 0x01, 0x03]).unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        vec![0x30, 0x03, 0x0a, 0x01, 0x02],
+        stapled(config.get_inner(), "blog.braindead.fr")
+    );
+    assert_eq!(
+        vec![0x30, 0x03, 0x0a, 0x01, 0x03],
+        stapled(config.get_inner(), "shop.braindead.fr")
+    );
+}
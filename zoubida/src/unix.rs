@@ -0,0 +1,76 @@
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use axum::Router;
+use tokio::net::UnixListener;
+
+/// Binds `path`, replacing the socket a previous run may have left behind
+///
+/// The socket is only accessible to the owner and group, to be shared with the proxy.
+pub fn listener(path: &Path) -> anyhow::Result<UnixListener> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)
+            .with_context(|| format!("unable to remove stale socket {path:?}"))?,
+        Ok(_) => bail!("{path:?} exists and is not a socket"),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+        Err(error) => return Err(error).with_context(|| format!("unable to stat {path:?}")),
+    }
+
+    let listener = UnixListener::bind(path).with_context(|| format!("failed to bind {path:?}"))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o660))
+        .with_context(|| format!("unable to set permissions of {path:?}"))?;
+    Ok(listener)
+}
+
+/// Serves `app` until SIGINT/SIGTERM, then drains connections for up to `timeout`
+pub async fn serve(listener: UnixListener, app: Router, timeout: Duration) -> anyhow::Result<()> {
+    let accept = hyper::server::accept::poll_fn(move |cx| {
+        listener
+            .poll_accept(cx)
+            .map(|accepted| Some(accepted.map(|(stream, _)| stream)))
+    });
+
+    let (signalled, on_signal) = tokio::sync::oneshot::channel();
+    let server = axum::Server::builder(accept)
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(async move {
+            crate::shutdown_signal().await;
+            tracing::info!("shutting down, draining connections for up to {timeout:?}");
+            let _ = signalled.send(());
+        });
+
+    // hyper drains connections without limit
+    let deadline = async move {
+        match on_signal.await {
+            Ok(()) => tokio::time::sleep(timeout).await,
+            Err(_) => std::future::pending().await,
+        }
+    };
+
+    tokio::select! {
+        served = server => served?,
+        _ = deadline => tracing::warn!("shutdown timeout elapsed, dropping remaining connections"),
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_listener() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("zoubida.sock");
+
+    let first = listener(&path).unwrap();
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(0o660, mode & 0o777);
+    drop(first);
+
+    // the stale socket is replaced
+    assert!(listener(&path).is_ok());
+
+    let file = dir.path().join("not-a-socket");
+    std::fs::write(&file, "precious").unwrap();
+    assert!(listener(&file).is_err());
+    assert_eq!("precious", std::fs::read_to_string(&file).unwrap());
+}
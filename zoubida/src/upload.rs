@@ -0,0 +1,330 @@
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use axum::body::{Body, HttpBody};
+use axum::extract::{Path as UrlPath, State};
+use axum::http::{header, HeaderMap, Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use tokio::io::AsyncWriteExt;
+
+use crate::{AppState, ServeMode};
+
+/// Route prefix of the upload endpoint, followed by the name of the site
+pub const UPLOAD_PATH: &str = "/__upload";
+
+/// Settings of the upload endpoint, enabled by `--upload-token`
+pub struct Upload {
+    pub token: String,
+    /// limit of both the request body and the extracted files, in bytes
+    pub max_size: u64,
+}
+
+/// Replaces the site `name` with the files of the (possibly gzipped) tar archive in the body
+///
+/// The archive is written then extracted in [`staging_dir`], never in the served directory.
+/// With `If-None-Match: *`, an existing site is left untouched and 412 is answered instead.
+pub async fn put(
+    State(state): State<Arc<AppState>>,
+    UrlPath(name): UrlPath<String>,
+    request: Request<Body>,
+) -> Response {
+    let Some(upload) = &state.upload else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if !authorized(request.headers(), &upload.token) {
+        return (StatusCode::UNAUTHORIZED, "invalid upload token").into_response();
+    }
+    if !crate::is_safe_site(&name) {
+        return (StatusCode::BAD_REQUEST, "invalid site name").into_response();
+    }
+
+    let too_large = (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        "archive exceeds the upload size limit",
+    );
+    let content_length = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok()?.parse::<u64>().ok());
+    if content_length.is_some_and(|length| length > upload.max_size) {
+        return too_large.into_response();
+    }
+
+    let root = match &state.mode {
+        ServeMode::Path(root) | ServeMode::Subdomain(root) => root.clone(),
+        // refused at startup
+        ServeMode::File(_) => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let if_none_match = request.headers().get(header::IF_NONE_MATCH);
+    if if_none_match.is_some_and(|value| value == "*")
+        && tokio::fs::try_exists(root.join(&name))
+            .await
+            .unwrap_or(true)
+    {
+        return (StatusCode::PRECONDITION_FAILED, "site already exists").into_response();
+    }
+    let staging = match staging_dir(&root).await {
+        Ok(staging) => staging,
+        Err(error) => {
+            tracing::error!("unable to stage the upload of site {name:?}: {error:#}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    static UPLOADS: AtomicU64 = AtomicU64::new(0);
+    let n = UPLOADS.fetch_add(1, Ordering::Relaxed);
+    let archive = staging.join(format!("{n}-{name}.tar"));
+
+    let received = receive(request.into_body(), &archive, upload.max_size).await;
+    let installed = match received {
+        Ok(true) => {
+            let (max_size, site) = (upload.max_size, name.clone());
+            let (archive, staging) = (archive.clone(), staging.join(format!("{n}-{name}")));
+            tokio::task::spawn_blocking(move || install(&root, &site, &archive, &staging, max_size))
+                .await
+        }
+        Ok(false) => {
+            let _ = tokio::fs::remove_file(&archive).await;
+            return too_large.into_response();
+        }
+        Err(error) => {
+            let _ = tokio::fs::remove_file(&archive).await;
+            tracing::warn!("unable to receive the upload of site {name:?}: {error:#}");
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+    };
+    let _ = tokio::fs::remove_file(&archive).await;
+    match installed {
+        Ok(Ok(())) => {
+            tracing::info!("site {name:?} uploaded");
+            (StatusCode::CREATED, format!("site {name} published")).into_response()
+        }
+        Ok(Err(error)) => {
+            tracing::warn!("rejected upload of site {name:?}: {error:#}");
+            (StatusCode::BAD_REQUEST, format!("{error:#}")).into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+fn authorized(headers: &HeaderMap, token: &str) -> bool {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| {
+            crate::auth::constant_time_eq(given.trim().as_bytes(), token.as_bytes())
+        })
+}
+
+/// Directory the uploads are staged in, next to `root` so that sites can be renamed from it
+///
+/// It is named `.<root>.uploads`, and lives outside of `root` to never be served.
+async fn staging_dir(root: &Path) -> anyhow::Result<PathBuf> {
+    let root = tokio::fs::canonicalize(root)
+        .await
+        .with_context(|| format!("unable to resolve {root:?}"))?;
+    let (Some(parent), Some(name)) = (root.parent(), root.file_name()) else {
+        bail!("{root:?} has no parent to stage uploads in");
+    };
+    let staging = parent.join(format!(".{}.uploads", name.to_string_lossy()));
+    tokio::fs::create_dir_all(&staging)
+        .await
+        .with_context(|| format!("unable to create {staging:?}"))?;
+    Ok(staging)
+}
+
+/// Writes `body` to `archive`, `false` when it exceeds `max_size`
+async fn receive(mut body: Body, archive: &Path, max_size: u64) -> anyhow::Result<bool> {
+    let mut file = tokio::fs::File::create(archive)
+        .await
+        .with_context(|| format!("unable to create {archive:?}"))?;
+    let mut size = 0;
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.context("unable to read the archive")?;
+        size += chunk.len() as u64;
+        if size > max_size {
+            return Ok(false);
+        }
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    Ok(true)
+}
+
+/// Extracts `archive` into `staging`, then swaps it with the site in a single step
+fn install(
+    root: &Path,
+    name: &str,
+    archive: &Path,
+    staging: &Path,
+    max_size: u64,
+) -> anyhow::Result<()> {
+    let site = root.join(name);
+
+    std::fs::create_dir(staging).with_context(|| format!("unable to create {staging:?}"))?;
+    let extracted = std::fs::File::open(archive)
+        .with_context(|| format!("unable to open {archive:?}"))
+        .and_then(|archive| extract(archive, staging, max_size));
+    if let Err(error) = extracted {
+        let _ = std::fs::remove_dir_all(staging);
+        return Err(error);
+    }
+
+    if site.exists() {
+        // `staging` holds the previous site once exchanged
+        exchange(staging, &site).context("unable to replace the previous site")?;
+        std::fs::remove_dir_all(staging).context("unable to remove the previous site")?;
+    } else {
+        std::fs::rename(staging, &site).context("unable to move the uploaded site")?;
+    }
+    Ok(())
+}
+
+/// Atomically swaps the paths `a` and `b`, so that requests never see a missing site
+#[cfg(target_os = "linux")]
+fn exchange(a: &Path, b: &Path) -> std::io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let a = CString::new(a.as_os_str().as_bytes())?;
+    let b = CString::new(b.as_os_str().as_bytes())?;
+    // SAFETY: both paths are NUL-terminated strings living until the call returns
+    let res = unsafe {
+        libc::renameat2(
+            libc::AT_FDCWD,
+            a.as_ptr(),
+            libc::AT_FDCWD,
+            b.as_ptr(),
+            libc::RENAME_EXCHANGE,
+        )
+    };
+    if res == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Swaps the paths `a` and `b` with two renames, the site being missing in between
+#[cfg(not(target_os = "linux"))]
+fn exchange(a: &Path, b: &Path) -> std::io::Result<()> {
+    let swap = a.with_extension("swap");
+    std::fs::rename(b, &swap)?;
+    std::fs::rename(a, b)?;
+    std::fs::rename(&swap, a)
+}
+
+/// Unpacks the files and directories of `archive` into `dir`, refusing links and paths escaping it
+fn extract(archive: impl Read, dir: &Path, max_size: u64) -> anyhow::Result<()> {
+    let mut archive = BufReader::new(archive);
+    let gzipped = archive
+        .fill_buf()
+        .context("unable to read the archive")?
+        .starts_with(&[0x1f, 0x8b]);
+    let reader: Box<dyn Read + '_> = if gzipped {
+        Box::new(flate2::read::GzDecoder::new(archive))
+    } else {
+        Box::new(archive)
+    };
+
+    let mut total = 0;
+    for entry in tar::Archive::new(reader)
+        .entries()
+        .context("invalid archive")?
+    {
+        let mut entry = entry.context("invalid archive")?;
+        let path = entry.path().context("invalid archive")?.into_owned();
+        let Some(relative) = sanitize(&path) else {
+            bail!("unsafe path {path:?} in archive");
+        };
+        let target = dir.join(relative);
+
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else if entry_type.is_file() {
+            total += entry.size();
+            if total > max_size {
+                bail!("archive exceeds the upload size limit");
+            }
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut file = std::fs::File::create(&target)?;
+            std::io::copy(&mut entry, &mut file).context("invalid archive")?;
+        } else if !entry_type.is_pax_global_extensions() && !entry_type.is_pax_local_extensions() {
+            bail!("unsupported entry {path:?}, only files and directories can be uploaded");
+        }
+    }
+    Ok(())
+}
+
+/// `path` when it stays below the extraction directory
+fn sanitize(path: &Path) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(segment) => relative.push(segment),
+            Component::CurDir => {}
+            Component::Prefix(_) | Component::RootDir | Component::ParentDir => return None,
+        }
+    }
+    Some(relative)
+}
+
+#[cfg(test)]
+fn tarball(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for (path, content) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        // bypasses the checks of `set_path` to build malicious archives
+        let name = &mut header.as_old_mut().name;
+        name[..path.len()].copy_from_slice(path.as_bytes());
+        header.set_cksum();
+        builder.append(&header, *content).unwrap();
+    }
+    builder.into_inner().unwrap()
+}
+
+#[test]
+fn test_extract() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = tarball(&[("index.html", b"hi"), ("./assets/app.js", b"app")]);
+    extract(&archive[..], dir.path(), 1024).unwrap();
+    assert_eq!(
+        "hi",
+        std::fs::read_to_string(dir.path().join("index.html")).unwrap()
+    );
+    assert_eq!(
+        "app",
+        std::fs::read_to_string(dir.path().join("assets/app.js")).unwrap()
+    );
+
+    let evil = tarball(&[("../evil.html", b"pwned")]);
+    assert!(extract(&evil[..], &dir.path().join("assets"), 1024).is_err());
+    assert!(!dir.path().join("evil.html").exists());
+    assert!(extract(&tarball(&[("/etc/passwd", b"")])[..], dir.path(), 1024).is_err());
+
+    // the limit applies to the extracted size, whatever the compression
+    assert!(extract(&tarball(&[("big.bin", &[0; 2048])])[..], dir.path(), 1024).is_err());
+}
+
+#[test]
+fn test_exchange() {
+    let dir = tempfile::tempdir().unwrap();
+    let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+    std::fs::create_dir(&a).unwrap();
+    std::fs::write(a.join("new.html"), "new").unwrap();
+    std::fs::create_dir(&b).unwrap();
+    std::fs::write(b.join("old.html"), "old").unwrap();
+
+    exchange(&a, &b).unwrap();
+    assert!(b.join("new.html").exists());
+    assert!(a.join("old.html").exists());
+    assert!(exchange(&a, &dir.path().join("missing")).is_err());
+}
@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+
+/// Loads a TOML map of `"<host>" = "<dir>"`, relative directories being relative to the file
+pub fn load(path: &Path) -> anyhow::Result<HashMap<String, PathBuf>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("unable to read vhosts file {path:?}"))?;
+    let hosts: HashMap<String, PathBuf> =
+        toml::from_str(&content).with_context(|| format!("invalid vhosts file {path:?}"))?;
+
+    let base = path.parent().unwrap_or(Path::new("."));
+    let mut vhosts = HashMap::new();
+    for (host, dir) in hosts {
+        let dir = base.join(dir);
+        if !dir.is_dir() {
+            bail!("unable to find directory {dir:?} of vhost {host:?}");
+        }
+        vhosts.insert(host.to_ascii_lowercase(), dir);
+    }
+    Ok(vhosts)
+}
+
+#[test]
+fn test_load() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("example")).unwrap();
+    let file = dir.path().join("vhosts.toml");
+    std::fs::write(
+        &file,
+        "\"Example.com\" = \"example\"\n\"www.example.com\" = \"example\"\n",
+    )
+    .unwrap();
+
+    let vhosts = load(&file).unwrap();
+    assert_eq!(dir.path().join("example"), vhosts["example.com"]);
+    assert_eq!(dir.path().join("example"), vhosts["www.example.com"]);
+
+    std::fs::write(&file, "\"blog.example.com\" = \"missing\"\n").unwrap();
+    assert!(load(&file).is_err());
+}